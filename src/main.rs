@@ -1,8 +1,6 @@
-use macroquad::{
-    input,
-    prelude::*,
-    ui::{hash, root_ui, widgets},
-}; // 0.8.
+use macroquad::prelude::*; // 0.8.
+
+use simulation::{Config, Simulation};
 
 mod simulation;
 
@@ -19,9 +17,58 @@ fn window_conf() -> Conf {
     }
 }
 
-#[macroquad::main(window_conf)]
-async fn main() {
-    let mut simulation = simulation::Simulation::new(WIDTH as f32, HEIGHT as f32);
+/// The value following `flag` on the command line, if any.
+fn flag_value<'a>(args: &'a [String], flag: &str) -> Option<&'a str> {
+    args.iter()
+        .position(|a| a == flag)
+        .and_then(|i| args.get(i + 1))
+        .map(|v| v.as_str())
+}
+
+/// Usage:
+///
+///     learn-macroquad [--config FILE] [--headless [--generations N]] [--dump-config]
+///
+/// `--headless` runs N generations in generational mode without opening a
+/// window, printing a CSV summary line per species and generation. The config file
+/// doubles as the scenario: it lists the species and who eats whom.
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    let config = match flag_value(&args, "--config") {
+        Some(path) => Config::load(path).unwrap_or_else(|e| {
+            eprintln!("could not load config {}", e);
+            std::process::exit(1);
+        }),
+        None => Config::default(),
+    };
+
+    if args.iter().any(|a| a == "--dump-config") {
+        println!("{}", serde_json::to_string_pretty(&config).unwrap());
+    } else if args.iter().any(|a| a == "--headless") {
+        let generations = flag_value(&args, "--generations")
+            .map(|n| {
+                n.parse().unwrap_or_else(|_| {
+                    eprintln!("--generations expects a number, got {}", n);
+                    std::process::exit(1);
+                })
+            })
+            .unwrap_or(10);
+        world(config).run_headless(generations);
+    } else {
+        macroquad::Window::from_config(window_conf(), run(world(config)));
+    }
+}
+
+/// The simulation `config` describes, or exits explaining why there is none.
+fn world(config: Config) -> Simulation {
+    Simulation::new(WIDTH as f32, HEIGHT as f32, config).unwrap_or_else(|e| {
+        eprintln!("invalid config: {}", e);
+        std::process::exit(1);
+    })
+}
+
+async fn run(mut simulation: Simulation) {
     simulation.is_running = true;
 
     while simulation.is_running {
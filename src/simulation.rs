@@ -1,232 +1,590 @@
-use std::f32::consts::PI;
-
-use ::rand::{
-    distributions::{Distribution, Standard},
-    Rng,
+use std::{
+    collections::{HashMap, HashSet},
+    f32::consts::PI,
 };
 
 use macroquad::prelude::*;
 
-use crate::{HEIGHT, WIDTH};
+use behaviour::Weighted;
+use brain::Brain;
+pub use config::Config;
+use config::{ControllerKind, Fitness, Mode};
+use genes::Genes;
+use kin::Sharing;
+use mating::{Courtship, Reproduction};
+use memory::Memory;
+use mortality::Deaths;
+use neat::{Genome, Innovations, Speciation};
+use obstacle::Obstacle;
+use pack::Hunts;
+use pheromone::Pheromones;
+use signal::{Signal, SignalKind};
+use species::{FoodWeb, Species};
+use terrain::{PerCell, Terrain, TerrainEffect};
+use wander::Wandering;
+
+mod behaviour;
+mod brain;
+mod config;
+mod edges;
+mod generation;
+mod genes;
+mod kin;
+mod mating;
+mod memory;
+mod mortality;
+mod neat;
+mod obstacle;
+mod pack;
+mod pheromone;
+mod signal;
+mod species;
+mod terrain;
+mod wander;
 
-const ENTITY_DETECT_RANGE: f32 = 100.0;
-const ENTITY_SIZE: f32 = 5.0;
 const FOOD_SIZE: f32 = 3.0;
 
-trait QuadTreeItem: Clone {
+pub trait QuadTreeItem: Clone {
     fn pos(&self) -> Vec2;
+    /// Moves the item by `offset`, e.g. to show a copy across a wrapped edge.
+    fn shift(&mut self, offset: Vec2);
     fn draw(&self);
     fn debug(&self);
 }
 
 #[derive(Debug, Clone)]
 pub struct Entity {
+    id: u64,
     pos: Vec2,
-    coop: f32,
-    share: f32,
+    genes: Genes,
+    controller: Controller,
+    /// Heading in radians.
     direction: f32,
-    food_collected: u32,
-    group: EntityType,
+    /// Current speed in pixels per tick, up to `max_speed()` times
+    /// `panic_speed_boost` while sprinting away from a threat.
+    speed: f32,
+    /// Total nutrition eaten so far.
+    food_collected: f32,
+    /// Ticks lived so far.
+    age: u32,
+    /// Ticks lived since it was spawned; founders are spawned at any age.
+    survived: u32,
+    energy: f32,
+    /// Ticks left digesting the last kill; a handling predator neither moves nor hunts.
+    handling: u32,
+    /// Set while standing still with a partner, waiting for a child.
+    courtship: Option<Courtship>,
+    /// Ticks left before the entity may court again.
+    cooldown: u32,
+    /// Whether the entity is looking for a mate, refreshed every tick.
+    ready_to_mate: bool,
+    /// The prey its pack is after, for entities that hunt in packs.
+    quarry: Option<u64>,
+    memory: Memory,
+    /// How far along its search the entity is.
+    wandering: Wandering,
+    /// Index into the scenario's species.
+    species: usize,
+    /// Body radius, copied from the species.
+    size: f32,
+    colour: Color,
+    /// Age at which the entity grows up, copied from the species.
+    maturity_age: u32,
+    /// Fraction of top speed a juvenile reaches, copied from the species.
+    juvenile_speed: f32,
     is_eaten: bool,
+    /// Whether old age claimed the entity this tick.
+    expired: bool,
 }
 
+/// What turns an entity's perception into a decision each tick.
 #[derive(Debug, Clone)]
-enum EntityType {
-    Predator,
-    Prey,
+pub enum Controller {
+    /// The species' weighted steering behaviours.
+    Behaviours,
+    /// A heritable feed-forward network.
+    NeuralNet(Brain),
+    /// A NEAT genome whose topology evolves too.
+    Neat(Genome),
 }
 
-impl Distribution<EntityType> for Standard {
-    fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> EntityType {
-        // match rng.gen_range(0, 3) { // rand 0.5, 0.6, 0.7
-        match rng.gen_range(0..=1) {
-            // rand 0.8
-            0 => EntityType::Predator,
-            1 => EntityType::Prey,
-            _ => EntityType::Prey,
+impl Controller {
+    fn new(kind: &ControllerKind, config: &Config) -> Self {
+        match kind {
+            ControllerKind::Behaviours => Controller::Behaviours,
+            ControllerKind::NeuralNet => {
+                Controller::NeuralNet(Brain::random(&config.brain_hidden_layers))
+            }
+            ControllerKind::Neat => Controller::Neat(Genome::minimal()),
+        }
+    }
+
+    /// A child controller mixing two parents, `self` being the fitter one.
+    /// Parents with different kinds of controller can't be mixed, so the
+    /// child copies `self`.
+    fn crossover(&self, other: &Controller) -> Self {
+        match (self, other) {
+            (Controller::NeuralNet(ours), Controller::NeuralNet(theirs)) => {
+                Controller::NeuralNet(ours.crossover(theirs))
+            }
+            (Controller::Neat(ours), Controller::Neat(theirs)) => {
+                Controller::Neat(ours.crossover(theirs))
+            }
+            _ => self.clone(),
+        }
+    }
+
+    /// The controller an offspring inherits.
+    fn mutate(&self, config: &Config, innovations: &mut Innovations) -> Self {
+        match self {
+            Controller::Behaviours => Controller::Behaviours,
+            Controller::NeuralNet(brain) => Controller::NeuralNet(
+                brain.mutate(config.brain_mutation_rate, config.brain_mutation_strength),
+            ),
+            Controller::Neat(genome) => Controller::Neat(genome.mutate(config, innovations)),
         }
     }
 }
 
+/// Everything an entity can see during the sense phase of a tick.
+///
+/// Built from the quadtrees as they were at the start of the tick, so every
+/// entity perceives the same world regardless of its index in `entities`.
+#[derive(Debug, Default)]
+pub struct Perception {
+    /// Where the things this entity eats are, nearest first.
+    targets: Vec<Vec2>,
+    /// Entities this one eats, nearest first.
+    prey: Vec<Entity>,
+    /// Entities that eat this one, nearest first.
+    threats: Vec<Entity>,
+    /// Other entities of the same species, nearest first.
+    neighbours: Vec<Entity>,
+    /// The nearest point of every obstacle within vision range, nearest first.
+    obstacles: Vec<Vec2>,
+    /// Gradient of the scent of everything this entity eats.
+    prey_scent: Vec2,
+    /// Gradient of the scent of its own species.
+    kin_scent: Vec2,
+    /// Where others of its species raised the alarm last tick.
+    alarms: Vec<Vec2>,
+    /// Where others of its species called out food last tick.
+    calls: Vec<Vec2>,
+    /// What the entity remembers, every place moved to its copy nearest it.
+    memory: Memory,
+}
+
+/// What an entity decided to do this tick, applied in the act phase.
+#[derive(Debug, Clone)]
+struct Decision {
+    direction: f32,
+    /// Speed to travel at this tick, already limited by acceleration.
+    speed: f32,
+    /// Energy spent on top of normal movement, e.g. for a panicked sprint.
+    energy_cost: f32,
+    /// The prey to hunt along with the pack.
+    quarry: Option<u64>,
+    /// The call to make this tick, if any.
+    signal: Option<SignalKind>,
+    /// What the entity will remember next tick.
+    memory: Memory,
+    /// Where its search stands next tick.
+    wandering: Wandering,
+}
+
 impl QuadTreeItem for Entity {
     fn pos(&self) -> Vec2 {
         self.pos
     }
+    fn shift(&mut self, offset: Vec2) {
+        self.pos += offset;
+    }
     fn draw(&self) {
-        draw_circle(
-            self.pos.x,
-            self.pos.y,
-            ENTITY_SIZE,
-            match &self.group {
-                EntityType::Predator => RED,
-                EntityType::Prey => BLUE,
-            },
-        );
+        draw_circle(self.pos.x, self.pos.y, self.size, self.colour);
 
         draw_line(
             self.pos.x,
             self.pos.y,
-            self.pos.x + ENTITY_SIZE * 1.5 * self.direction.cos(),
-            self.pos.y + ENTITY_SIZE * 1.5 * self.direction.sin(),
+            self.pos.x + self.size * 1.5 * self.direction.cos(),
+            self.pos.y + self.size * 1.5 * self.direction.sin(),
             2.0,
             WHITE,
         );
     }
 
     fn debug(&self) {
-        // draw_circle_lines(self.pos.x, self.pos.y, ENTITY_DETECT_RANGE, 2.0, WHITE);
+        let Genes {
+            vision_range,
+            vision_angle,
+            hearing_range,
+            ..
+        } = self.genes;
+        let colour = Color::new(1.0, 1.0, 1.0, 0.3);
 
-        // draw_rectangle_lines(
-        //     self.pos.x - ENTITY_DETECT_RANGE,
-        //     self.pos.y - ENTITY_DETECT_RANGE,
-        //     2.0 * ENTITY_DETECT_RANGE,
-        //     2.0 * ENTITY_DETECT_RANGE,
-        //     2.0,
-        //     WHITE,
-        // );
+        draw_arc(
+            self.pos.x,
+            self.pos.y,
+            40,
+            vision_range,
+            (self.direction - vision_angle / 2.0).to_degrees(),
+            1.0,
+            vision_angle.to_degrees(),
+            colour,
+        );
+        if vision_angle < 2.0 * PI {
+            for side in [-0.5, 0.5] {
+                let edge = self.pos
+                    + vision_range * Vec2::from_angle(self.direction + side * vision_angle);
+                draw_line(self.pos.x, self.pos.y, edge.x, edge.y, 1.0, colour);
+            }
+        }
+        draw_circle_lines(self.pos.x, self.pos.y, hearing_range, 1.0, colour);
     }
 }
 
 impl Entity {
-    pub fn new(x: f32, y: f32, group: EntityType) -> Self {
+    pub fn new(
+        id: u64,
+        pos: Vec2,
+        species: usize,
+        kind: &Species,
+        genes: Genes,
+        controller: Controller,
+        energy: f32,
+    ) -> Self {
         Entity {
-            pos: Vec2 { x, y },
-            coop: rand::gen_range(0.0, 1.0),
-            share: rand::gen_range(0.0, 1.0),
-            group,
+            id,
+            pos,
+            genes,
+            controller,
+            species,
+            size: kind.size,
+            colour: kind.colour(),
+            maturity_age: kind.maturity_age,
+            juvenile_speed: kind.juvenile_speed,
             direction: rand::gen_range(-PI, PI),
-            food_collected: 0,
+            speed: 0.0,
+            food_collected: 0.0,
+            age: 0,
+            survived: 0,
+            energy,
+            handling: 0,
+            courtship: None,
+            cooldown: 0,
+            ready_to_mate: false,
+            quarry: None,
+            memory: Memory::new(pos),
+            wandering: Wandering::default(),
             is_eaten: false,
+            expired: false,
         }
     }
 
-    fn step(&mut self, entity_qt: &QuadTree<Entity>, foods_qt: &QuadTree<Food>) {
-        let Vec2 { x, y } = self.pos;
+    /// Juveniles are slower and can't reproduce.
+    fn is_juvenile(&self) -> bool {
+        self.age < self.maturity_age
+    }
 
-        let close_entities: Vec<Entity> = entity_qt
-            .query(Rect {
-                x: x - ENTITY_DETECT_RANGE,
-                y: y - ENTITY_DETECT_RANGE,
-                w: 2.0 * ENTITY_DETECT_RANGE,
-                h: 2.0 * ENTITY_DETECT_RANGE,
-            })
+    /// Top speed at the entity's current age.
+    fn max_speed(&self) -> f32 {
+        if self.is_juvenile() {
+            self.genes.max_speed * self.juvenile_speed
+        } else {
+            self.genes.max_speed
+        }
+    }
+
+    /// Score at the end of a generation, weighted as configured.
+    fn fitness(&self, fitness: &Fitness) -> f32 {
+        fitness.food * self.food_collected
+            + fitness.survival * self.survived as f32
+            + fitness.energy * self.energy.max(0.0)
+    }
+
+    fn velocity(&self) -> Vec2 {
+        self.speed * Vec2::from_angle(self.direction)
+    }
+
+    /// Splits off an offspring with slightly mutated genes and half the energy.
+    fn reproduce(
+        &mut self,
+        id: u64,
+        kind: &Species,
+        config: &Config,
+        innovations: &mut Innovations,
+    ) -> Entity {
+        self.energy /= 2.0;
+        let offset = self.size * 2.0 * Vec2::from_angle(rand::gen_range(-PI, PI));
+        Entity::new(
+            id,
+            self.pos + offset,
+            self.species,
+            kind,
+            self.genes.mutate(config.mutation_rate),
+            self.controller.mutate(config, innovations),
+            self.energy,
+        )
+    }
+
+    /// Whether `pos` is within hearing range, or inside the vision cone with
+    /// no obstacle in the way. Obstacles don't block hearing; terrain at `pos`
+    /// shortens the vision range as set by `effects`.
+    fn perceives(
+        &self,
+        pos: Vec2,
+        obstacles: &[Obstacle],
+        terrain: &Terrain,
+        effects: &PerCell<TerrainEffect>,
+    ) -> bool {
+        let offset = pos - self.pos;
+        let distance_squared = offset.length_squared();
+        if distance_squared <= self.genes.hearing_range.powi(2) {
+            return true;
+        }
+        let vision_range = self.genes.vision_range * effects.get(terrain.at(pos)).visibility;
+        distance_squared <= vision_range.powi(2)
+            && angle_between(self.direction, offset.to_angle()).abs()
+                <= self.genes.vision_angle / 2.0
+            && !obstacle::blocked(obstacles, self.pos, pos)
+    }
+
+    fn sense(
+        &self,
+        entity_qt: &QuadTree<Entity>,
+        foods_qt: &QuadTree<Food>,
+        web: &FoodWeb,
+        obstacles: &[Obstacle],
+        terrain: &Terrain,
+        effects: &PerCell<TerrainEffect>,
+    ) -> Perception {
+        let reach = self.genes.vision_range.max(self.genes.hearing_range);
+
+        let mut entities: Vec<Entity> = entity_qt
+            .query_around(self.pos, reach)
             .into_iter()
-            .filter(|e| {
-                self.pos.distance_squared(e.pos) <= ENTITY_DETECT_RANGE * ENTITY_DETECT_RANGE
-            })
+            .filter(|e| e.id != self.id && self.perceives(e.pos, obstacles, terrain, effects))
             .collect();
+        entities.sort_by(|a, b| {
+            self.pos
+                .distance_squared(a.pos)
+                .partial_cmp(&self.pos.distance_squared(b.pos))
+                .unwrap()
+        });
 
-        match &self.group {
-            EntityType::Prey => {
-                let mut close_foods: Vec<Food> = foods_qt
-                    .query(Rect::new(
-                        x - ENTITY_DETECT_RANGE,
-                        y - ENTITY_DETECT_RANGE,
-                        2.0 * ENTITY_DETECT_RANGE,
-                        2.0 * ENTITY_DETECT_RANGE,
-                    ))
+        let prey: Vec<Entity> = entities
+            .iter()
+            .filter(|e| web.eats(self.species, e.species))
+            .cloned()
+            .collect();
+        let mut targets: Vec<Vec2> = prey.iter().map(|e| e.pos).collect();
+        if web.forages(self.species) {
+            targets.extend(
+                foods_qt
+                    .query_around(self.pos, reach)
                     .into_iter()
-                    .filter(|e| self.pos.distance_squared(e.pos) <= ENTITY_DETECT_RANGE.powi(2))
-                    .collect();
+                    .filter(|f| web.eats_food(self.species, f.kind))
+                    .map(|f| f.pos)
+                    .filter(|pos| self.perceives(*pos, obstacles, terrain, effects)),
+            );
+        }
+        targets.sort_by(|a, b| {
+            self.pos
+                .distance_squared(*a)
+                .partial_cmp(&self.pos.distance_squared(*b))
+                .unwrap()
+        });
 
-                close_foods.sort_by(|a, b| {
-                    self.pos
-                        .distance(a.pos)
-                        .partial_cmp(&self.pos.distance(b.pos))
-                        .unwrap()
-                });
+        let (neighbours, others): (Vec<Entity>, Vec<Entity>) = entities
+            .into_iter()
+            .partition(|e| e.species == self.species);
+        let threats = others
+            .into_iter()
+            .filter(|e| web.eats(e.species, self.species))
+            .collect();
 
-                if close_foods.len() > 0 {
-                    for food in close_foods[0..=(2.min(close_foods.len() - 1))].iter() {
-                        let dir =
-                            (food.pos.y - self.pos.y).atan2(close_foods[0].pos.x - self.pos.x);
-                        self.direction += steer(self.direction, dir)
-                            / self.pos.distance(food.pos)
-                            / (if self.food_collected != 0 {
-                                self.food_collected as f32
-                            } else {
-                                1.0
-                            })
-                            .powf(1.5);
-                    }
-                }
+        let mut nearby: Vec<Vec2> = obstacles
+            .iter()
+            .map(|o| o.surface(self.pos))
+            .filter(|p| self.pos.distance_squared(*p) <= self.genes.vision_range.powi(2))
+            .collect();
+        nearby.sort_by(|a, b| {
+            self.pos
+                .distance_squared(*a)
+                .partial_cmp(&self.pos.distance_squared(*b))
+                .unwrap()
+        });
 
-                let mut close_predators: Vec<Entity> = close_entities
-                    .into_iter()
-                    .filter(|e| match e.group {
-                        EntityType::Prey => false,
-                        EntityType::Predator => true,
-                    })
-                    .collect();
-
-                close_predators.sort_by(|a, b| {
-                    self.pos
-                        .distance(a.pos)
-                        .partial_cmp(&self.pos.distance(b.pos))
-                        .unwrap()
-                });
+        Perception {
+            targets,
+            prey,
+            threats,
+            neighbours,
+            obstacles: nearby,
+            ..Default::default()
+        }
+    }
 
-                if close_predators.len() > 0 {
-                    if self.pos.distance_squared(close_predators[0].pos)
-                        <= (ENTITY_SIZE * 2.0).powi(2)
-                    {
-                        self.is_eaten = true;
-                    }
-                }
-            }
-            EntityType::Predator => {
-                let mut close_preies: Vec<Entity> = close_entities
-                    .into_iter()
-                    .filter(|e| {
-                        (self.pos.distance_squared(e.pos)
-                            <= ENTITY_DETECT_RANGE * ENTITY_DETECT_RANGE)
-                            && match e.group {
-                                EntityType::Prey => true,
-                                EntityType::Predator => false,
-                            }
-                    })
-                    .collect();
-
-                close_preies.sort_by(|a, b| {
-                    self.pos
-                        .distance(a.pos)
-                        .partial_cmp(&self.pos.distance(b.pos))
-                        .unwrap()
-                });
+    fn decide(
+        &self,
+        perception: &Perception,
+        behaviours: &[Weighted],
+        config: &Config,
+        size: Vec2,
+    ) -> Decision {
+        let Genes {
+            max_turn_rate,
+            max_acceleration,
+            ..
+        } = self.genes;
 
-                if close_preies.len() > 0 {
-                    for food in close_preies[0..=(2.min(close_preies.len() - 1))].iter() {
-                        let Vec2 {
-                            x: food_x,
-                            y: food_y,
-                        } = food.pos();
-
-                        let dir =
-                            (food.pos.y - self.pos.y).atan2(close_preies[0].pos.x - self.pos.x);
-                        self.direction += steer(self.direction, dir)
-                            / self.pos.distance(food.pos)
-                            / (if self.food_collected != 0 {
-                                self.food_collected as f32
-                            } else {
-                                1.0
-                            })
-                            .powf(1.5);
-                    }
-                }
+        let wish = match &self.controller {
+            Controller::Behaviours => self.steer(perception, behaviours, config),
+            Controller::NeuralNet(brain) => {
+                self.obey(&brain.think(&brain::sensors(self, perception, config)))
             }
+            Controller::Neat(genome) => {
+                self.obey(&genome.think(&brain::sensors(self, perception, config)))
+            }
+        };
+
+        Decision {
+            direction: self.direction
+                + angle_between(self.direction, wish.direction)
+                    .clamp(-max_turn_rate, max_turn_rate),
+            speed: self.speed
+                + (wish.speed - self.speed).clamp(-max_acceleration, max_acceleration),
+            energy_cost: wish.energy_cost,
+            quarry: self.choose_quarry(perception, config),
+            signal: wish.signal,
+            memory: self.recall(perception, config, size),
+            wandering: wish.wandering,
         }
+    }
+
+    /// The hand-written controller: follows the species' behaviours, searches
+    /// the species' way when nothing is in sight to eat or flee, sprints away when a
+    /// threat gets too close and makes the calls its genes allow. Ignores turn
+    /// and acceleration limits.
+    fn steer(&self, perception: &Perception, behaviours: &[Weighted], config: &Config) -> Decision {
+        let max_speed = self.max_speed();
 
-        self.pos.x += 1.0 * self.direction.cos();
-        self.pos.y += 1.0 * self.direction.sin();
+        let desire = behaviour::compose(behaviours, self, perception);
+        // With nothing to go for or flee from, the search heading joins in
+        // whatever else the behaviours want, such as staying with the flock.
+        let searching = perception.targets.is_empty() && perception.threats.is_empty();
+        let (direction, wandering) = if searching {
+            let (heading, wandering) = self
+                .wandering
+                .next(&config.species[self.species].search, self.direction);
+            ((desire + Vec2::from_angle(heading)).to_angle(), wandering)
+        } else if desire != Vec2::ZERO {
+            (desire.to_angle(), self.wandering)
+        } else {
+            (self.direction, self.wandering)
+        };
+
+        let panic = self.energy > 0.0
+            && perception.threats.first().is_some_and(|threat| {
+                self.pos.distance_squared(threat.pos) <= config.panic_radius.powi(2)
+            });
 
-        self.pos.x = self.pos.x.rem_euclid(entity_qt.boundary.w);
-        self.pos.y = self.pos.y.rem_euclid(entity_qt.boundary.h);
+        let speed = if panic {
+            max_speed * config.panic_speed_boost
+        } else if perception.targets.is_empty() && perception.threats.is_empty() {
+            max_speed * config.cruise_speed
+        } else {
+            max_speed
+        };
+
+        Decision {
+            direction,
+            speed,
+            energy_cost: if panic { config.panic_energy_cost } else { 0.0 },
+            quarry: None,
+            signal: self.signal(perception),
+            memory: self.memory,
+            wandering,
+        }
+    }
+
+    /// Turns a network's outputs, each in `[-1, 1]`, into a turn and a speed.
+    /// Ignores turn and acceleration limits.
+    fn obey(&self, outputs: &[f32]) -> Decision {
+        Decision {
+            direction: self.direction + outputs[0] * self.genes.max_turn_rate,
+            speed: (outputs[1] + 1.0) / 2.0 * self.max_speed(),
+            energy_cost: 0.0,
+            quarry: None,
+            signal: None,
+            memory: self.memory,
+            wandering: self.wandering,
+        }
+    }
+
+    fn act(
+        &mut self,
+        decision: &Decision,
+        boundary: Rect,
+        obstacles: &[Obstacle],
+        effect: &TerrainEffect,
+        config: &Config,
+    ) {
+        self.age += 1;
+        self.survived += 1;
+        self.energy -= config.basal_metabolism;
+        self.cooldown = self.cooldown.saturating_sub(1);
+        self.quarry = decision.quarry;
+        self.memory = decision.memory;
+        self.wandering = decision.wandering;
+        if decision.signal.is_some() {
+            self.energy -= config.signal_cost;
+        }
+        if self.handling > 0 {
+            self.handling -= 1;
+            self.speed = 0.0;
+            return;
+        }
+        if let Some(courtship) = &mut self.courtship {
+            courtship.ticks_left = courtship.ticks_left.saturating_sub(1);
+            self.speed = 0.0;
+            return;
+        }
+
+        self.direction = decision.direction;
+        self.speed = decision.speed;
+        self.energy -=
+            decision.energy_cost + config.movement_cost * self.speed.powi(2) * effect.energy;
+
+        self.pos += self.velocity() * effect.speed;
+
+        for obstacle in obstacles.iter() {
+            if let Some(pos) = obstacle.push_out(self.pos, self.size) {
+                self.pos = pos;
+            }
+        }
+
+        config
+            .edges
+            .contain(&mut self.pos, &mut self.direction, boundary.size());
     }
 }
 
+/// Where a food item came from, which decides who can eat it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FoodKind {
+    /// Grows on its own.
+    Plant,
+    /// Left behind by a dead entity; rots away over time.
+    Carcass,
+}
+
 #[derive(Clone, Debug)]
 pub struct Food {
+    id: u64,
     pos: Vec2,
+    kind: FoodKind,
+    /// Nutrition left in this item; it is gone once this reaches zero.
+    nutrition: f32,
     is_eaten: bool,
 }
 
@@ -234,54 +592,74 @@ impl QuadTreeItem for Food {
     fn pos(&self) -> Vec2 {
         self.pos
     }
+    fn shift(&mut self, offset: Vec2) {
+        self.pos += offset;
+    }
     fn draw(&self) {
-        draw_circle(self.pos.x, self.pos.y, FOOD_SIZE, ORANGE);
+        match self.kind {
+            FoodKind::Plant => draw_circle(self.pos.x, self.pos.y, FOOD_SIZE, ORANGE),
+            FoodKind::Carcass => draw_circle(self.pos.x, self.pos.y, FOOD_SIZE, GRAY),
+        }
     }
     fn debug(&self) {}
 }
 
 impl Food {
-    pub fn new(x: f32, y: f32) -> Self {
+    pub fn new(id: u64, x: f32, y: f32, kind: FoodKind, nutrition: f32) -> Self {
         Food {
+            id,
             pos: Vec2 { x, y },
+            kind,
+            nutrition,
             is_eaten: false,
         }
     }
+}
 
-    fn step(&mut self, entity_qt: &QuadTree<Entity>) {
-        let Vec2 { x, y } = self.pos;
+/// An eater touching something it can eat at the end of the act phase, or a
+/// donor next to a relative it could feed.
+#[derive(Debug, Clone, Copy)]
+struct Claim {
+    eater: u64,
+    target: u64,
+    distance: f32,
+}
 
-        let mut close_entities: Vec<Entity> = entity_qt
-            .query(Rect::new(
-                x - ENTITY_DETECT_RANGE,
-                y - ENTITY_DETECT_RANGE,
-                2.0 * ENTITY_DETECT_RANGE,
-                2.0 * ENTITY_DETECT_RANGE,
-            ))
-            .into_iter()
-            .filter(|e| {
-                self.pos.distance_squared(e.pos) <= ENTITY_DETECT_RANGE * ENTITY_DETECT_RANGE
-                    && match e.group {
-                        EntityType::Predator => false,
-                        EntityType::Prey => true,
-                    }
-            })
-            .collect();
+/// An interaction settled in the resolve phase, applied to both parties at once.
+#[derive(Debug, Clone)]
+enum Event {
+    /// `predator` caught and ate `prey`.
+    Predation { predator: u64, prey: u64 },
+    /// `eater` took a bite out of the food item `food`.
+    Graze { eater: u64, food: u64 },
+    /// `donor` handed some of its energy to its relative `recipient`.
+    Share { donor: u64, recipient: u64 },
+}
 
-        close_entities.sort_by(|a, b| {
-            self.pos
-                .distance(a.pos)
-                .partial_cmp(&self.pos.distance(b.pos))
-                .unwrap()
-        });
+/// Settles simultaneous eats so the outcome does not depend on vector order.
+///
+/// Closest pairs win first (ties broken by id), every eater gets at most one
+/// target and every target is taken by at most one eater.
+fn resolve_claims(mut claims: Vec<Claim>) -> Vec<Claim> {
+    claims.sort_by(|a, b| {
+        a.distance
+            .partial_cmp(&b.distance)
+            .unwrap()
+            .then(a.eater.cmp(&b.eater))
+            .then(a.target.cmp(&b.target))
+    });
 
-        if close_entities.len() > 0 {
-            if self.pos.distance_squared(close_entities[0].pos) <= (ENTITY_SIZE + FOOD_SIZE).powi(2)
-            {
-                self.is_eaten = true;
-            }
+    let mut eaters = HashSet::new();
+    let mut targets = HashSet::new();
+    let mut resolved = vec![];
+    for claim in claims {
+        if !eaters.contains(&claim.eater) && !targets.contains(&claim.target) {
+            eaters.insert(claim.eater);
+            targets.insert(claim.target);
+            resolved.push(claim);
         }
     }
+    resolved
 }
 
 #[derive(Debug, Clone)]
@@ -350,6 +728,8 @@ pub struct QuadTree<T: QuadTreeItem> {
     items: Vec<T>,
     children: Option<Box<QuadTreeChildren<T>>>,
     divided: bool,
+    /// Whether `query_around` treats the boundary as a torus.
+    wraps: bool,
 }
 
 impl<T: QuadTreeItem> QuadTree<T> {
@@ -360,9 +740,16 @@ impl<T: QuadTreeItem> QuadTree<T> {
             items: vec![],
             children: None,
             divided: false,
+            wraps: false,
         }
     }
 
+    /// The same tree, with `query_around` seeing across the edges if `wraps`.
+    pub fn wrapping(mut self, wraps: bool) -> Self {
+        self.wraps = wraps;
+        self
+    }
+
     pub fn insert(&mut self, item: T) {
         if self.boundary.contains(item.pos()) {
             if (self.capacity >= self.items.len()) && (!self.divided) {
@@ -423,10 +810,42 @@ impl<T: QuadTreeItem> QuadTree<T> {
         found
     }
 
+    /// Items within `reach` of `centre` along each axis. In a wrapping tree,
+    /// items found across an edge come back shifted next to `centre`, so plain
+    /// distances to them are the shortest ones around the torus.
+    pub fn query_around(&self, centre: Vec2, reach: f32) -> Vec<T> {
+        let range = Rect::new(centre.x - reach, centre.y - reach, 2.0 * reach, 2.0 * reach);
+        if !self.wraps {
+            return self.query(range);
+        }
+        // Only look across the edges the range actually pokes over.
+        let Rect { x, y, w, h } = self.boundary;
+        let across = |low: bool, high: bool, side: f32| {
+            [(true, 0.0), (low, -side), (high, side)]
+                .into_iter()
+                .filter_map(|(over, shift)| over.then_some(shift))
+        };
+        let mut found = vec![];
+        for dx in across(range.left() < x, range.right() > x + w, w) {
+            for dy in across(range.top() < y, range.bottom() > y + h, h) {
+                let offset = Vec2::new(dx, dy);
+                found.extend(
+                    self.query(range.offset(-offset))
+                        .into_iter()
+                        .map(|mut item| {
+                            item.shift(offset);
+                            item
+                        }),
+                );
+            }
+        }
+        found
+    }
+
     fn show(&self) {
         let Rect { x, y, w, h } = self.boundary;
         draw_rectangle_lines(x, y, w, h, 2.0, BLUE);
-        if self.query(self.boundary).len() != 0 {
+        if !self.query(self.boundary).is_empty() {
             draw_text(
                 format!("{}", self.query(self.boundary).len()).as_str(),
                 x + w - 30.0,
@@ -467,13 +886,50 @@ pub struct Simulation {
     pub foods: Vec<Food>,
     boundary: Rect,
     capacity: usize,
+    next_id: u64,
+    config: Config,
+    food_web: FoodWeb,
+    /// Steering compositions, indexed by species.
+    behaviours: Vec<Vec<Weighted>>,
+    /// The species spawned by a left click.
+    selected_species: usize,
+    /// The scenario's obstacles plus any drawn with the mouse.
+    obstacles: Vec<Obstacle>,
+    /// Points of the wall being drawn with the mouse, if any.
+    wall: Option<Vec<[f32; 2]>>,
+    terrain: Terrain,
+    innovations: Innovations,
+    neat_species: Speciation,
+    /// The entity shown in the inspect panel.
+    inspected: Option<u64>,
+    generation: u32,
+    /// Ticks since the current generation started.
+    generation_tick: u32,
+    /// Entities that died during the current generation, kept for scoring.
+    fallen: Vec<Entity>,
+    /// Deaths by cause for each species, since the world was populated or,
+    /// in generational mode, since the generation started.
+    deaths: Vec<Deaths>,
+    /// Energy shared within each species, over the same span as `deaths`.
+    sharing: Vec<Sharing>,
+    /// Kills by lone and pack hunters of each species, over the same span.
+    hunts: Vec<Hunts>,
+    /// Scent left behind by every species.
+    pheromones: Pheromones,
+    /// Whether the selected species' scent is drawn as a heatmap.
+    heatmap: bool,
+    /// Calls made during the current tick.
+    signals: Vec<Signal>,
+    /// Calls made during the last tick, which entities hear this one.
+    signal_qt: QuadTree<Signal>,
     debug: bool,
     pause: bool,
 }
 
 impl Simulation {
-    pub fn new(w: f32, h: f32) -> Self {
-        Simulation {
+    /// A world of `w` by `h` pixels, or why `config` can't make one.
+    pub fn new(w: f32, h: f32, config: Config) -> Result<Self, String> {
+        Ok(Simulation {
             is_running: false,
             entities: vec![],
             entity_qt: QuadTree::new(
@@ -502,9 +958,48 @@ impl Simulation {
                 h,
             },
             capacity: 4,
+            next_id: 0,
+            food_web: FoodWeb::new(&config.species)?,
+            behaviours: config
+                .species
+                .iter()
+                .map(|s| s.build_behaviours())
+                .collect(),
+            selected_species: 0,
+            deaths: vec![Deaths::default(); config.species.len()],
+            sharing: vec![Sharing::default(); config.species.len()],
+            hunts: vec![Hunts::default(); config.species.len()],
+            obstacles: config.obstacles.clone(),
+            wall: None,
+            terrain: Terrain::new(&config.terrain, w, h)?,
+            pheromones: Pheromones::new(
+                &config.pheromones,
+                config.species.len(),
+                w,
+                h,
+                config.edges.wraps(),
+            ),
+            heatmap: false,
+            signals: vec![],
+            signal_qt: QuadTree::new(
+                Rect {
+                    x: 0.0,
+                    y: 0.0,
+                    w,
+                    h,
+                },
+                4,
+            ),
+            config,
+            innovations: Innovations::default(),
+            neat_species: Speciation::default(),
+            inspected: None,
+            generation: 0,
+            generation_tick: 0,
+            fallen: vec![],
             debug: false,
             pause: false,
-        }
+        })
     }
 
     fn clear(&self) {
@@ -529,26 +1024,341 @@ impl Simulation {
         let result = self.entity_qt.query(range).len();
         draw_rectangle_lines(x, y, w, h, 2.0, RED);
         draw_text(
-            format!("{}, {}, {:?}", self.entities.len(), result, self.pause).as_str(),
+            format!(
+                "{}, {}, {:?}, {:?}, generation {}, spawning {}",
+                self.entities.len(),
+                result,
+                self.pause,
+                self.config.controller,
+                self.generation,
+                self.config.species[self.selected_species].name
+            )
+            .as_str(),
+            30.0,
             30.0,
+            16.0,
+            WHITE,
+        );
+        let Deaths {
+            eaten,
+            starved,
+            old_age,
+            lost,
+        } = self.deaths[self.selected_species];
+        draw_text(
+            format!(
+                "deaths: {} eaten, {} starved, {} old age, {} lost",
+                eaten, starved, old_age, lost
+            )
+            .as_str(),
             30.0,
+            48.0,
+            16.0,
+            WHITE,
+        );
+        let sharing = &self.sharing[self.selected_species];
+        draw_text(
+            format!(
+                "sharing: {} shares, mean relatedness {:.2}",
+                sharing.shares,
+                sharing.mean_relatedness()
+            )
+            .as_str(),
+            30.0,
+            66.0,
+            16.0,
+            WHITE,
+        );
+        let Hunts { solo, pack } = self.hunts[self.selected_species];
+        draw_text(
+            format!("kills: {} solo, {} by packs", solo, pack).as_str(),
+            30.0,
+            84.0,
             16.0,
             WHITE,
         );
     }
 
-    fn update(&mut self) {
+    /// Details of the inspected entity in the top-right corner, with its
+    /// network drawn underneath when it has a NEAT controller.
+    fn draw_inspector(&self) {
+        let Some(entity) = self.entities.iter().find(|e| Some(e.id) == self.inspected) else {
+            return;
+        };
+
+        draw_circle_lines(entity.pos.x, entity.pos.y, entity.size * 2.0, 2.0, YELLOW);
+
+        let panel = Rect::new(self.boundary.w - 270.0, 10.0, 260.0, 360.0);
+        draw_rectangle(
+            panel.x,
+            panel.y,
+            panel.w,
+            panel.h,
+            Color::new(0.0, 0.0, 0.0, 0.8),
+        );
+        draw_rectangle_lines(panel.x, panel.y, panel.w, panel.h, 2.0, YELLOW);
+
+        let controller = match &entity.controller {
+            Controller::Behaviours => "behaviours".to_owned(),
+            Controller::NeuralNet(_) => "neural net".to_owned(),
+            Controller::Neat(genome) => format!(
+                "NEAT, species {} of {}",
+                genome.species,
+                self.neat_species.len()
+            ),
+        };
+        let lines = [
+            format!(
+                "#{} {}",
+                entity.id, self.config.species[entity.species].name
+            ),
+            format!(
+                "energy {:.2}, eaten {:.2}",
+                entity.energy, entity.food_collected
+            ),
+            format!(
+                "age {}{}",
+                entity.age,
+                if entity.is_juvenile() {
+                    " (juvenile)"
+                } else {
+                    ""
+                }
+            ),
+            format!(
+                "speed {:.2}/{:.2}, turn {:.2}",
+                entity.speed, entity.genes.max_speed, entity.genes.max_turn_rate
+            ),
+            format!(
+                "coop {:.2}, share {:.2}, kin {:016x}",
+                entity.genes.coop, entity.genes.share, entity.genes.kin[0]
+            ),
+            format!(
+                "alarm {:.2}, food call {:.2}, false call {:.2}",
+                entity.genes.alarm, entity.genes.food_call, entity.genes.false_call
+            ),
+            controller,
+        ];
+        for (i, line) in lines.iter().enumerate() {
+            draw_text(
+                line,
+                panel.x + 10.0,
+                panel.y + 20.0 + 18.0 * i as f32,
+                16.0,
+                WHITE,
+            );
+        }
+
+        if let Controller::Neat(genome) = &entity.controller {
+            let top = 20.0 + 18.0 * lines.len() as f32;
+            genome.draw(Rect::new(
+                panel.x + 10.0,
+                panel.y + top,
+                panel.w - 20.0,
+                panel.h - top - 10.0,
+            ));
+        }
+    }
+
+    /// Writes the inspected entity's NEAT genome to `genome-<id>.json`.
+    fn export_genome(&self) {
+        let Some(Entity {
+            id,
+            controller: Controller::Neat(genome),
+            ..
+        }) = self.entities.iter().find(|e| Some(e.id) == self.inspected)
+        else {
+            return;
+        };
+
+        let path = format!("genome-{}.json", id);
+        match serde_json::to_string_pretty(genome)
+            .map_err(|e| e.to_string())
+            .and_then(|json| std::fs::write(&path, json).map_err(|e| e.to_string()))
+        {
+            Ok(()) => eprintln!("exported {}", path),
+            Err(e) => eprintln!("could not export {}: {}", path, e),
+        }
+    }
+
+    fn next_id(&mut self) -> u64 {
+        self.next_id += 1;
+        self.next_id
+    }
+
+    fn spawn_entity(&mut self, x: f32, y: f32, species: usize) {
+        let id = self.next_id();
+        let kind = &self.config.species[species];
+        let genes = Genes::founder(&kind.genes, kind.random_social);
+        let mut controller = Controller::new(&self.config.controller, &self.config);
+        if let Controller::Neat(genome) = &mut controller {
+            genome.species = self
+                .neat_species
+                .assign(genome, self.config.neat_compatibility_threshold);
+        }
+        self.entities.push(Entity::new(
+            id,
+            self.config
+                .edges
+                .place(Vec2::new(x, y), self.boundary.size()),
+            species,
+            &self.config.species[species],
+            genes,
+            controller,
+            self.config.initial_energy,
+        ));
+    }
+
+    /// Replaces everything in the world with the configured starting population.
+    pub fn populate(&mut self) {
+        self.entities.clear();
+        self.foods.clear();
+        self.fallen.clear();
+        self.pheromones.clear();
+        self.signals.clear();
+        self.deaths = vec![Deaths::default(); self.config.species.len()];
+        self.sharing = vec![Sharing::default(); self.config.species.len()];
+        self.hunts = vec![Hunts::default(); self.config.species.len()];
+        self.obstacles = self.config.obstacles.clone();
+        self.generation = 0;
+        self.generation_tick = 0;
+        for species in 0..self.config.species.len() {
+            for _ in 0..self.config.species[species].initial_count {
+                let Vec2 { x, y } = self.random_spot();
+                self.spawn_entity(x, y, species);
+                // Founders come at all ages, so they aren't all juveniles at once.
+                if let Some(founder) = self.entities.last_mut() {
+                    founder.age = rand::gen_range(0, 2 * founder.maturity_age + 1);
+                }
+            }
+        }
+        self.seed_food();
+    }
+
+    /// Scatters `initial_food` items over the world, favouring fertile ground.
+    fn seed_food(&mut self) {
+        let mut placed = 0;
+        for _ in 0..self.config.initial_food * 100 {
+            if placed == self.config.initial_food {
+                break;
+            }
+            if let Some(Vec2 { x, y }) = self.fertile_spot() {
+                self.spawn_food(x, y);
+                placed += 1;
+            }
+        }
+    }
+
+    /// A random spot where food takes root, or `None` if the terrain there
+    /// turned out to be too barren this time.
+    fn fertile_spot(&self) -> Option<Vec2> {
+        let spot = self.random_spot();
+        let fertility = *self.config.terrain.fertility.get(self.terrain.at(spot));
+        (rand::gen_range(0.0, 1.0) < fertility).then_some(spot)
+    }
+
+    /// A random position that isn't inside an obstacle, if one turns up
+    /// within a reasonable number of tries.
+    fn random_spot(&self) -> Vec2 {
+        let mut spot = Vec2::ZERO;
+        for _ in 0..100 {
+            spot = Vec2::new(
+                rand::gen_range(0.0, self.boundary.w),
+                rand::gen_range(0.0, self.boundary.h),
+            );
+            if !self.obstacles.iter().any(|o| o.contains(spot)) {
+                break;
+            }
+        }
+        spot
+    }
+
+    /// Shift + left drag draws a wall, shift + right click drops a boulder.
+    fn draw_obstacles_with_mouse(&mut self) {
+        let mouse = Vec2::from(mouse_position());
+        if is_mouse_button_down(MouseButton::Left) {
+            let wall = self.wall.get_or_insert_with(Vec::new);
+            if wall
+                .last()
+                .is_none_or(|last| mouse.distance(Vec2::from(*last)) >= 10.0)
+            {
+                wall.push(mouse.into());
+            }
+        } else if let Some(points) = self.wall.take() {
+            if points.len() >= 2 {
+                self.obstacles.push(Obstacle::Polyline {
+                    points,
+                    thickness: 6.0,
+                });
+            }
+        }
+        if is_mouse_button_pressed(MouseButton::Right) {
+            self.obstacles.push(Obstacle::Circle {
+                centre: mouse.into(),
+                radius: 20.0,
+            });
+        }
+    }
+
+    fn spawn_food(&mut self, x: f32, y: f32) {
+        let id = self.next_id();
+        let pos = self
+            .config
+            .edges
+            .place(Vec2::new(x, y), self.boundary.size());
+        self.foods.push(Food::new(
+            id,
+            pos.x,
+            pos.y,
+            FoodKind::Plant,
+            self.config.food_nutrition,
+        ));
+    }
+
+    /// Leaves a carcass where `entity` died. Killed entities only leave the
+    /// `carcass_leftover` share of their nutrition the predator didn't eat,
+    /// and those lost over an absorbing edge leave nothing.
+    fn spawn_carcass(&mut self, entity: &Entity) {
+        let mut nutrition = self.config.species[entity.species].nutrition;
+        if entity.is_eaten {
+            nutrition *= self.config.carcass_leftover;
+        }
+        if nutrition > 0.0 && self.boundary.contains(entity.pos) {
+            let id = self.next_id();
+            self.foods.push(Food::new(
+                id,
+                entity.pos.x,
+                entity.pos.y,
+                FoodKind::Carcass,
+                nutrition,
+            ));
+        }
+    }
+
+    /// Carcasses lose nutrition every tick and disappear once it is gone.
+    fn decay_carcasses(&mut self) {
+        for food in self.foods.iter_mut() {
+            if food.kind == FoodKind::Carcass {
+                food.nutrition -= self.config.carcass_decay;
+            }
+        }
+        self.foods.retain(|f| f.nutrition > 0.0);
+    }
+
+    /// Left click spawns the selected species, right click the next one and
+    /// middle click food. Holding control keeps spawning.
+    fn spawn_with_mouse(&mut self) {
         if is_mouse_button_pressed(MouseButton::Left)
             || (is_mouse_button_down(MouseButton::Left) && is_key_down(KeyCode::LeftControl))
         {
             let (x, y) = mouse_position();
             let rand_len = ((rand::gen_range(0.0, 1.0) as f64).sqrt() as f32) * 20.0;
             let rand_dir = rand::gen_range(-PI, PI);
-            self.entities.push(Entity::new(
+            self.spawn_entity(
                 x + rand_len * rand_dir.cos(),
                 y + rand_len * rand_dir.sin(),
-                EntityType::Prey,
-            ));
+                self.selected_species,
+            );
         }
         if is_mouse_button_pressed(MouseButton::Right)
             || (is_mouse_button_down(MouseButton::Right) && is_key_down(KeyCode::LeftControl))
@@ -556,46 +1366,83 @@ impl Simulation {
             let (x, y) = mouse_position();
             let rand_len = ((rand::gen_range(0.0, 1.0) as f64).sqrt() as f32) * 20.0;
             let rand_dir = rand::gen_range(-PI, PI);
-            self.entities.push(Entity::new(
+            self.spawn_entity(
                 x + rand_len * rand_dir.cos(),
                 y + rand_len * rand_dir.sin(),
-                EntityType::Predator,
-            ));
+                (self.selected_species + 1) % self.config.species.len(),
+            );
         }
         if is_mouse_button_down(MouseButton::Middle) {
             let (x, y) = mouse_position();
             let rand_len = ((rand::gen_range(0.0, 1.0) as f64).sqrt() as f32) * 20.0;
             let rand_dir = rand::gen_range(-PI, PI);
-            self.foods.push(Food::new(
-                x + rand_len * rand_dir.cos(),
-                y + rand_len * rand_dir.sin(),
-            ));
+            self.spawn_food(x + rand_len * rand_dir.cos(), y + rand_len * rand_dir.sin());
         }
+    }
 
+    fn handle_input(&mut self) {
+        if is_key_down(KeyCode::LeftShift) || self.wall.is_some() {
+            self.draw_obstacles_with_mouse();
+        } else {
+            self.spawn_with_mouse();
+        }
+
+        let number_keys = [
+            KeyCode::Key1,
+            KeyCode::Key2,
+            KeyCode::Key3,
+            KeyCode::Key4,
+            KeyCode::Key5,
+            KeyCode::Key6,
+            KeyCode::Key7,
+            KeyCode::Key8,
+            KeyCode::Key9,
+        ];
+        for (species, key) in number_keys
+            .into_iter()
+            .take(self.config.species.len())
+            .enumerate()
+        {
+            if is_key_pressed(key) {
+                self.selected_species = species;
+            }
+        }
         if is_key_pressed(KeyCode::R) {
-            self.entities.clear();
-            self.foods.clear();
-            for _ in 0..500 {
-                self.entities.push(Entity::new(
-                    rand::gen_range(0.0, WIDTH as f32),
-                    rand::gen_range(0.0, HEIGHT as f32),
-                    EntityType::Prey,
-                ));
-            }
-            for _ in 0..500 {
-                self.entities.push(Entity::new(
-                    rand::gen_range(0.0, WIDTH as f32),
-                    rand::gen_range(0.0, HEIGHT as f32),
-                    EntityType::Predator,
-                ));
-            }
-            for _ in 0..500 {
-                self.foods.push(Food::new(
-                    rand::gen_range(0.0, WIDTH as f32),
-                    rand::gen_range(0.0, HEIGHT as f32),
-                ));
+            self.populate();
+        }
+        if is_key_pressed(KeyCode::H) && self.config.mode == Mode::Generational {
+            let generation = self.generation;
+            while self.generation == generation {
+                self.tick();
             }
         }
+        if is_key_pressed(KeyCode::B) {
+            self.config.controller = match self.config.controller {
+                ControllerKind::Behaviours => ControllerKind::NeuralNet,
+                ControllerKind::NeuralNet => ControllerKind::Neat,
+                ControllerKind::Neat => ControllerKind::Behaviours,
+            };
+        }
+        if is_key_pressed(KeyCode::I) {
+            let mouse = Vec2::from(mouse_position());
+            self.inspected = self
+                .entities
+                .iter()
+                .filter(|e| e.pos.distance(mouse) <= e.size * 4.0)
+                .min_by(|a, b| {
+                    a.pos
+                        .distance(mouse)
+                        .partial_cmp(&b.pos.distance(mouse))
+                        .unwrap()
+                })
+                .map(|e| e.id);
+        }
+        if is_key_pressed(KeyCode::E) {
+            self.export_genome();
+        }
+        if is_key_pressed(KeyCode::S) {
+            self.heatmap = !self.heatmap;
+        }
         if is_key_pressed(KeyCode::D) {
             self.debug = !self.debug;
         }
@@ -605,55 +1452,488 @@ impl Simulation {
         if is_key_pressed(KeyCode::Space) {
             self.pause = !self.pause;
         }
+    }
 
-        self.entity_qt = QuadTree::new(self.boundary.clone(), self.capacity);
-        self.entities = self
+    fn rebuild_quadtrees(&mut self) {
+        self.entity_qt =
+            QuadTree::new(self.boundary, self.capacity).wrapping(self.config.edges.wraps());
+        for entity in self.entities.iter() {
+            self.entity_qt.insert(entity.clone());
+        }
+        self.food_qt =
+            QuadTree::new(self.boundary, self.capacity).wrapping(self.config.edges.wraps());
+        for food in self.foods.iter() {
+            self.food_qt.insert(food.clone());
+        }
+    }
+
+    /// Advances the world by one tick: sense → decide → act → resolve.
+    ///
+    /// Sensing and deciding read a snapshot taken before anyone moves, and eats
+    /// are settled together afterwards, so no entity sees a half-updated world.
+    fn tick(&mut self) {
+        self.rebuild_quadtrees();
+        self.broadcast();
+        let decisions: Vec<Decision> = self
             .entities
-            .clone()
+            .iter()
+            .map(|entity| {
+                let mut perception = entity.sense(
+                    &self.entity_qt,
+                    &self.food_qt,
+                    &self.food_web,
+                    &self.obstacles,
+                    &self.terrain,
+                    &self.config.species[entity.species].terrain,
+                );
+                entity.smell(&self.pheromones, &self.food_web, &mut perception);
+                entity.hear(&self.signal_qt, self.config.signal_radius, &mut perception);
+                entity.remember(self.config.edges, self.boundary.size(), &mut perception);
+                entity.decide(
+                    &perception,
+                    &self.behaviours[entity.species],
+                    &self.config,
+                    self.boundary.size(),
+                )
+            })
+            .collect();
+
+        for (entity, decision) in self.entities.iter_mut().zip(decisions.iter()) {
+            let effect = self.config.species[entity.species]
+                .terrain
+                .get(self.terrain.at(entity.pos));
+            entity.act(
+                decision,
+                self.boundary,
+                &self.obstacles,
+                effect,
+                &self.config,
+            );
+        }
+
+        for (entity, decision) in self.entities.iter().zip(decisions.iter()) {
+            if let Some(kind) = decision.signal {
+                self.signals.push(Signal {
+                    kind,
+                    pos: entity.pos,
+                    sender: entity.id,
+                    species: entity.species,
+                    radius: self.config.signal_radius,
+                });
+            }
+        }
+        for entity in self.entities.iter() {
+            let scent = self.config.species[entity.species].scent;
+            self.pheromones.deposit(entity.species, entity.pos, scent);
+        }
+        self.pheromones.update(&self.config.pheromones);
+
+        self.rebuild_quadtrees();
+        let events = self.resolve();
+        self.apply(&events);
+        self.senesce();
+
+        let (alive, dead): (Vec<Entity>, Vec<Entity>) = std::mem::take(&mut self.entities)
             .into_iter()
-            .filter(|a| !a.is_eaten)
+            .partition(|e| e.death(self.boundary).is_none());
+        self.entities = alive;
+        for entity in dead.iter() {
+            if let Some(death) = entity.death(self.boundary) {
+                self.deaths[entity.species].record(death);
+            }
+        }
+        self.separate();
+        self.foods.retain(|f| !f.is_eaten);
+        self.decay_carcasses();
+        for entity in dead.iter() {
+            self.spawn_carcass(entity);
+        }
+
+        match self.config.mode {
+            Mode::Continuous => self.reproduce(),
+            Mode::Generational => {
+                self.fallen.extend(dead);
+                self.generation_tick += 1;
+                if self.generation_tick >= self.config.generation_ticks {
+                    self.next_generation();
+                }
+            }
+        }
+        self.grow_food();
+    }
+
+    /// Every grown-up entity of an asexual species with enough energy splits
+    /// off an offspring; sexual species go through `mate`.
+    fn reproduce(&mut self) {
+        let mut offspring = self.mate();
+        for i in 0..self.entities.len() {
+            let species = &self.config.species[self.entities[i].species];
+            if species.reproduction == Reproduction::Asexual
+                && !self.entities[i].is_juvenile()
+                && self.entities[i].energy >= self.config.reproduction_threshold
+            {
+                let id = self.next_id();
+                let kind = &self.config.species[self.entities[i].species];
+                let mut child =
+                    self.entities[i].reproduce(id, kind, &self.config, &mut self.innovations);
+                child.pos = self.config.edges.place(child.pos, self.boundary.size());
+                if let Controller::Neat(genome) = &mut child.controller {
+                    genome.species = self
+                        .neat_species
+                        .assign(genome, self.config.neat_compatibility_threshold);
+                }
+                offspring.push(child);
+            }
+        }
+        self.entities.extend(offspring);
+        self.prune_neat_species();
+    }
+
+    /// Forgets the NEAT species no living genome belongs to any more.
+    fn prune_neat_species(&mut self) {
+        let alive = self
+            .entities
+            .iter()
+            .filter_map(|e| match &e.controller {
+                Controller::Neat(genome) => Some(genome.species),
+                _ => None,
+            })
             .collect();
-        for entity in self.entities.iter_mut() {
-            if !self.pause {
-                entity.step(&self.entity_qt, &self.food_qt);
+        self.neat_species.prune(&alive);
+    }
+
+    /// Sprouts new food at random spots, `food_growth_rate` items per tick on
+    /// average over grass; less fertile terrain grows less.
+    fn grow_food(&mut self) {
+        let mut budget = self.config.food_growth_rate;
+        while budget > 0.0 {
+            if budget >= 1.0 || rand::gen_range(0.0, 1.0) < budget {
+                if let Some(Vec2 { x, y }) = self.fertile_spot() {
+                    self.spawn_food(x, y);
+                }
             }
-            self.entity_qt.insert(entity.clone());
+            budget -= 1.0;
         }
-        self.food_qt = QuadTree::new(self.boundary.clone(), self.capacity);
-        self.foods = self
-            .foods
-            .clone()
+    }
+
+    /// Finds every eater in contact with something it eats and settles who
+    /// gets what. Predation is settled first so freshly caught prey don't eat.
+    fn resolve(&self) -> Vec<Event> {
+        let largest = self
+            .config
+            .species
+            .iter()
+            .map(|s| s.size)
+            .fold(0.0, f32::max);
+
+        let mut predations = vec![];
+        for predator in self.entities.iter().filter(|e| e.handling == 0) {
+            for prey in self
+                .entity_qt
+                .query_around(predator.pos, predator.size + largest)
+                .into_iter()
+                .filter(|e| self.food_web.eats(predator.species, e.species))
+            {
+                let distance = predator.pos.distance(prey.pos);
+                if distance <= predator.size + prey.size {
+                    predations.push(Claim {
+                        eater: predator.id,
+                        target: prey.id,
+                        distance,
+                    });
+                }
+            }
+        }
+        let mut events: Vec<Event> = resolve_claims(predations)
             .into_iter()
-            .filter(|a| !a.is_eaten)
+            .map(|c| Event::Predation {
+                predator: c.eater,
+                prey: c.target,
+            })
             .collect();
-        for food in self.foods.iter_mut() {
-            if !self.pause {
-                food.step(&self.entity_qt);
+        let caught: HashSet<u64> = events
+            .iter()
+            .filter_map(|e| match e {
+                Event::Predation { prey, .. } => Some(*prey),
+                _ => None,
+            })
+            .collect();
+
+        let mut grazes = vec![];
+        for eater in self
+            .entities
+            .iter()
+            .filter(|e| self.food_web.forages(e.species) && !caught.contains(&e.id))
+        {
+            let reach = eater.size + FOOD_SIZE;
+            for food in self
+                .food_qt
+                .query_around(eater.pos, reach)
+                .into_iter()
+                .filter(|f| self.food_web.eats_food(eater.species, f.kind))
+            {
+                let distance = eater.pos.distance(food.pos);
+                if distance <= reach {
+                    grazes.push(Claim {
+                        eater: eater.id,
+                        target: food.id,
+                        distance,
+                    });
+                }
+            }
+        }
+        events.extend(resolve_claims(grazes).into_iter().map(|c| Event::Graze {
+            eater: c.eater,
+            food: c.target,
+        }));
+        events.extend(
+            resolve_claims(self.share_claims(&caught))
+                .into_iter()
+                .map(|c| Event::Share {
+                    donor: c.eater,
+                    recipient: c.target,
+                }),
+        );
+
+        events
+    }
+
+    /// Pushes overlapping survivors apart. Each pair gives way by the overlap
+    /// times the lower of their species' stiffness, split so the heavier body
+    /// moves less. Runs after eating so contact still allows a catch.
+    fn separate(&mut self) {
+        let largest = self
+            .config
+            .species
+            .iter()
+            .map(|s| s.size)
+            .fold(0.0, f32::max);
+        let index: HashMap<u64, usize> = self
+            .entities
+            .iter()
+            .enumerate()
+            .map(|(i, e)| (e.id, i))
+            .collect();
+
+        let mut pushes = vec![Vec2::ZERO; self.entities.len()];
+        for (i, a) in self.entities.iter().enumerate() {
+            let stiffness = self.config.species[a.species].stiffness;
+            if stiffness <= 0.0 {
+                continue;
+            }
+            for b in self.entity_qt.query_around(a.pos, a.size + largest) {
+                if b.id == a.id || !index.contains_key(&b.id) {
+                    continue;
+                }
+                let offset = a.pos - b.pos;
+                let overlap = a.size + b.size - offset.length();
+                if overlap <= 0.0 {
+                    continue;
+                }
+                let direction = if offset == Vec2::ZERO {
+                    // Bodies on the same spot split along an axis picked by the
+                    // pair, so each of them is pushed the opposite way.
+                    let (low, high) = (a.id.min(b.id), a.id.max(b.id));
+                    let axis = Vec2::from_angle((low.wrapping_mul(31) ^ high) as f32);
+                    if a.id == low {
+                        axis
+                    } else {
+                        -axis
+                    }
+                } else {
+                    offset.normalize()
+                };
+                let share = b.size.powi(2) / (a.size.powi(2) + b.size.powi(2));
+                pushes[i] += direction
+                    * overlap
+                    * share
+                    * stiffness.min(self.config.species[b.species].stiffness);
+            }
+        }
+
+        let size = self.boundary.size();
+        for (entity, push) in self.entities.iter_mut().zip(pushes) {
+            if push == Vec2::ZERO {
+                continue;
             }
-            if !food.is_eaten {
-                self.food_qt.insert(food.clone());
+            // A body buried in a crowd moves at most its own radius per tick.
+            entity.pos += push.clamp_length_max(entity.size);
+            for obstacle in self.obstacles.iter() {
+                if let Some(pos) = obstacle.push_out(entity.pos, entity.size) {
+                    entity.pos = pos;
+                }
             }
-            // food.step(&self.food_qt);
+            entity.pos = self.config.edges.place(entity.pos, size);
         }
     }
 
+    /// Applies the settled events to both parties of each interaction.
+    fn apply(&mut self, events: &[Event]) {
+        let entity_index: HashMap<u64, usize> = self
+            .entities
+            .iter()
+            .enumerate()
+            .map(|(i, e)| (e.id, i))
+            .collect();
+        let food_index: HashMap<u64, usize> = self
+            .foods
+            .iter()
+            .enumerate()
+            .map(|(i, f)| (f.id, i))
+            .collect();
+
+        for event in events {
+            match *event {
+                Event::Predation { predator, prey } => {
+                    // A predator that got a share of a packmate's kill earlier
+                    // this tick is busy eating it and lets its own catch go.
+                    if self.entities[entity_index[&predator]].handling > 0 {
+                        continue;
+                    }
+                    let pack = self.pack(&self.entities[entity_index[&predator]], prey);
+                    let species = self.entities[entity_index[&predator]].species;
+                    let prey = &mut self.entities[entity_index[&prey]];
+                    prey.is_eaten = true;
+                    // What the predators don't eat is left on the carcass.
+                    let nutrition = self.config.species[prey.species].nutrition
+                        * (1.0 - self.config.carcass_leftover);
+
+                    // A pack splits the kill evenly among everyone at it, leaving
+                    // out packmates that started on another kill earlier this tick.
+                    let mut eaters: Vec<u64> = pack
+                        .into_iter()
+                        .filter(|id| {
+                            *id == predator || self.entities[entity_index[id]].handling == 0
+                        })
+                        .collect();
+                    if eaters.len() > 1 {
+                        self.hunts[species].pack += 1;
+                    } else {
+                        self.hunts[species].solo += 1;
+                        eaters = vec![predator];
+                    }
+                    let share = nutrition / eaters.len() as f32;
+                    for eater in eaters {
+                        let eater = &mut self.entities[entity_index[&eater]];
+                        eater.food_collected += share;
+                        eater.energy += share;
+                        eater.handling = self.config.handling_time;
+                    }
+                }
+                Event::Graze { eater, food } => {
+                    let food = &mut self.foods[food_index[&food]];
+                    let bite = food.nutrition.min(self.config.bite_size);
+                    food.nutrition -= bite;
+                    food.is_eaten = food.nutrition <= 0.0;
+                    let eater = &mut self.entities[entity_index[&eater]];
+                    eater.food_collected += bite;
+                    eater.energy += bite;
+                }
+                Event::Share { donor, recipient } => {
+                    let (donor, recipient) = (entity_index[&donor], entity_index[&recipient]);
+                    let relatedness = self.entities[donor]
+                        .genes
+                        .relatedness(&self.entities[recipient].genes);
+                    let gift =
+                        (self.config.share_amount * self.entities[donor].genes.share * relatedness)
+                            .min(self.entities[donor].energy - self.config.share_threshold);
+                    if gift <= 0.0 {
+                        continue;
+                    }
+                    self.entities[donor].energy -= gift;
+                    self.entities[recipient].energy += gift;
+                    self.sharing[self.entities[donor].species].record(relatedness);
+                }
+            }
+        }
+    }
+
+    fn update(&mut self) {
+        self.handle_input();
+
+        if !self.pause {
+            self.tick();
+        }
+        self.rebuild_quadtrees();
+    }
+
     pub fn frame(&mut self) {
         self.clear();
         self.update();
+        self.terrain.draw();
+        if self.heatmap {
+            self.pheromones.draw(
+                self.selected_species,
+                self.config.species[self.selected_species].colour(),
+            );
+        }
+        if !self.config.edges.wraps() {
+            let Rect { x, y, w, h } = self.boundary;
+            draw_rectangle_lines(x, y, w, h, 4.0, GRAY);
+        }
+        for obstacle in self.obstacles.iter() {
+            obstacle.draw();
+        }
+        if let Some(wall) = &self.wall {
+            for s in wall.windows(2) {
+                draw_line(s[0][0], s[0][1], s[1][0], s[1][1], 6.0, GRAY);
+            }
+        }
         self.draw();
         self.entity_qt.draw();
         self.food_qt.draw();
         if self.debug {
+            self.signal_qt.draw();
+            self.draw_memories();
             self.entity_qt.show();
             self.food_qt.show();
         }
+        self.draw_inspector();
     }
 }
 
-fn steer(dir0: f32, dir: f32) -> f32 {
-    if (dir - dir0).rem_euclid(2.0 * PI) < PI {
-        1.0
-    } else {
-        -1.0
+/// The signed turn, in `[-PI, PI)`, that takes heading `from` to heading `to`.
+fn angle_between(from: f32, to: f32) -> f32 {
+    (to - from + PI).rem_euclid(2.0 * PI) - PI
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn claim(eater: u64, target: u64, distance: f32) -> Claim {
+        Claim {
+            eater,
+            target,
+            distance,
+        }
+    }
+
+    fn outcome(claims: Vec<Claim>) -> Vec<(u64, u64)> {
+        let mut pairs: Vec<(u64, u64)> = resolve_claims(claims)
+            .iter()
+            .map(|c| (c.eater, c.target))
+            .collect();
+        pairs.sort();
+        pairs
+    }
+
+    #[test]
+    fn resolve_claims_does_not_depend_on_order() {
+        // Eaters 1 and 2 tie for target 10, eater 2 also reaches 11 and
+        // eater 3 ties with it there.
+        let claims = vec![
+            claim(1, 10, 2.0),
+            claim(2, 10, 2.0),
+            claim(2, 11, 3.0),
+            claim(3, 11, 3.0),
+            claim(3, 12, 5.0),
+        ];
+        let mut reversed = claims.clone();
+        reversed.reverse();
+
+        let resolved = outcome(claims);
+        assert_eq!(resolved, outcome(reversed));
+        assert_eq!(resolved, vec![(1, 10), (2, 11), (3, 12)]);
     }
 }
@@ -0,0 +1,304 @@
+use macroquad::prelude::*;
+
+use super::{Entity, Perception};
+
+/// A single steering rule.
+///
+/// Returns the heading the entity would like to take, scaled by how strongly
+/// it wants it. A zero vector means the rule has nothing to say this tick.
+pub trait Behaviour {
+    fn steer(&self, entity: &Entity, perception: &Perception) -> Vec2;
+}
+
+/// A behaviour together with how much it counts in a species' composition.
+pub struct Weighted {
+    pub weight: f32,
+    pub behaviour: Box<dyn Behaviour>,
+}
+
+impl Weighted {
+    pub fn new(weight: f32, behaviour: impl Behaviour + 'static) -> Self {
+        Weighted {
+            weight,
+            behaviour: Box::new(behaviour),
+        }
+    }
+}
+
+/// Sums every behaviour's opinion, scaled by its weight.
+pub fn compose(behaviours: &[Weighted], entity: &Entity, perception: &Perception) -> Vec2 {
+    behaviours
+        .iter()
+        .map(|w| w.weight * w.behaviour.steer(entity, perception))
+        .fold(Vec2::ZERO, |a, b| a + b)
+}
+
+/// Heading from `from` towards `to`, scaled down by the distance between
+/// them so that nearer things pull harder.
+fn towards(from: Vec2, to: Vec2) -> Vec2 {
+    let offset = to - from;
+    offset.normalize_or_zero() / offset.length().max(1.0)
+}
+
+/// The entity pulled towards every one of `points`.
+fn pull(entity: &Entity, points: impl IntoIterator<Item = Vec2>) -> Vec2 {
+    points
+        .into_iter()
+        .map(|p| towards(entity.pos, p))
+        .fold(Vec2::ZERO, |a, b| a + b)
+}
+
+/// The entity pushed away from every one of `points`.
+fn push(entity: &Entity, points: impl IntoIterator<Item = Vec2>) -> Vec2 {
+    -pull(entity, points)
+}
+
+/// How much food still draws the entity: less the more it has eaten.
+fn appetite(entity: &Entity) -> f32 {
+    1.0 / entity.food_collected.max(1.0).powf(1.5)
+}
+
+/// Heads for the nearest few things the entity eats, closer ones pulling
+/// harder. Well-fed entities are pulled less.
+pub struct Seek;
+
+impl Behaviour for Seek {
+    fn steer(&self, entity: &Entity, perception: &Perception) -> Vec2 {
+        appetite(entity) * pull(entity, perception.targets.iter().take(3).copied())
+    }
+}
+
+/// Heads away from everything that eats the entity. Every threat in sight
+/// adds its own push, and closer ones push harder.
+pub struct Flee;
+
+impl Behaviour for Flee {
+    fn steer(&self, entity: &Entity, perception: &Perception) -> Vec2 {
+        push(entity, perception.threats.iter().map(|threat| threat.pos))
+    }
+}
+
+/// Matches the average heading of same-kind neighbours.
+pub struct Align;
+
+impl Behaviour for Align {
+    fn steer(&self, _entity: &Entity, perception: &Perception) -> Vec2 {
+        perception
+            .neighbours
+            .iter()
+            .map(|n| Vec2::from_angle(n.direction))
+            .fold(Vec2::ZERO, |a, b| a + b)
+            .normalize_or_zero()
+    }
+}
+
+/// Heads for the centre of same-kind neighbours.
+pub struct Cohere;
+
+impl Behaviour for Cohere {
+    fn steer(&self, entity: &Entity, perception: &Perception) -> Vec2 {
+        if perception.neighbours.is_empty() {
+            return Vec2::ZERO;
+        }
+        let centre = perception
+            .neighbours
+            .iter()
+            .map(|n| n.pos)
+            .fold(Vec2::ZERO, |a, b| a + b)
+            / perception.neighbours.len() as f32;
+        (centre - entity.pos).normalize_or_zero()
+    }
+}
+
+/// Keeps clear of same-kind neighbours closer than `radius`.
+pub struct Separate {
+    pub radius: f32,
+}
+
+impl Behaviour for Separate {
+    fn steer(&self, entity: &Entity, perception: &Perception) -> Vec2 {
+        push(
+            entity,
+            perception
+                .neighbours
+                .iter()
+                .map(|n| n.pos)
+                .filter(|p| entity.pos.distance_squared(*p) < self.radius.powi(2)),
+        )
+    }
+}
+
+/// Turns away from obstacle surfaces closer than `range`, harder the closer
+/// they are.
+pub struct AvoidObstacles {
+    pub range: f32,
+}
+
+impl Behaviour for AvoidObstacles {
+    fn steer(&self, entity: &Entity, perception: &Perception) -> Vec2 {
+        push(
+            entity,
+            perception
+                .obstacles
+                .iter()
+                .copied()
+                .filter(|p| entity.pos.distance_squared(*p) < self.range.powi(2)),
+        )
+    }
+}
+
+/// Heads for the nearest neighbour that is also looking for a mate, as long
+/// as the entity is looking too.
+pub struct SeekMate;
+
+impl Behaviour for SeekMate {
+    fn steer(&self, entity: &Entity, perception: &Perception) -> Vec2 {
+        if !entity.ready_to_mate {
+            return Vec2::ZERO;
+        }
+        perception
+            .neighbours
+            .iter()
+            .find(|n| n.ready_to_mate)
+            .map_or(Vec2::ZERO, |n| (n.pos - entity.pos).normalize_or_zero())
+    }
+}
+
+/// Heads for neighbours with less energy than the entity, pulled harder by
+/// closer relatives and by a higher `share` gene.
+pub struct ShareFood;
+
+impl Behaviour for ShareFood {
+    fn steer(&self, entity: &Entity, perception: &Perception) -> Vec2 {
+        perception
+            .neighbours
+            .iter()
+            .filter(|n| n.energy < entity.energy)
+            .map(|n| entity.genes.relatedness(&n.genes) * towards(entity.pos, n.pos))
+            .fold(Vec2::ZERO, |a, b| a + b)
+            * entity.genes.share
+    }
+}
+
+/// For pack hunters: closes in on the pack's quarry from the side the rest
+/// of the pack leaves open. The ring around the quarry starts `radius` wide
+/// and tightens as the pack spreads around it; a hunter with no packmates
+/// on the quarry heads straight for it.
+pub struct Encircle {
+    pub radius: f32,
+}
+
+impl Behaviour for Encircle {
+    fn steer(&self, entity: &Entity, perception: &Perception) -> Vec2 {
+        let Some(quarry) = entity
+            .quarry
+            .and_then(|id| perception.prey.iter().find(|p| p.id == id))
+        else {
+            return Vec2::ZERO;
+        };
+        let bearing = |p: Vec2| (p - quarry.pos).normalize_or_zero();
+        let mates: Vec<Vec2> = perception
+            .neighbours
+            .iter()
+            .filter(|n| n.quarry == entity.quarry)
+            .map(|n| bearing(n.pos))
+            .collect();
+        if mates.is_empty() {
+            return (quarry.pos - entity.pos).normalize_or_zero();
+        }
+
+        let mine = bearing(entity.pos);
+        let others = mates.iter().fold(Vec2::ZERO, |a, b| a + *b);
+        let side = (mine * mates.len() as f32 - others)
+            .try_normalize()
+            .unwrap_or(mine);
+        // 1 with the whole pack on one side of the quarry, 0 once surrounded.
+        let spread = (mine + others).length() / (mates.len() + 1) as f32;
+        let spot = quarry.pos + side * self.radius * spread;
+        (spot - entity.pos).normalize_or_zero()
+    }
+}
+
+/// Boids: keeps clear of neighbours closer than `separation` while matching
+/// their heading and drifting to their centre. Alignment and cohesion are
+/// scaled by the `coop` gene, so loners only keep their distance.
+pub struct Flock {
+    pub separation: f32,
+    pub alignment: f32,
+    pub cohesion: f32,
+}
+
+impl Behaviour for Flock {
+    fn steer(&self, entity: &Entity, perception: &Perception) -> Vec2 {
+        let herd = self.alignment * Align.steer(entity, perception)
+            + self.cohesion * Cohere.steer(entity, perception);
+        Separate {
+            radius: self.separation,
+        }
+        .steer(entity, perception)
+            + entity.genes.coop * herd
+    }
+}
+
+/// Follows the scent of prey uphill, towards where it is fresher.
+pub struct Track;
+
+impl Behaviour for Track {
+    fn steer(&self, _entity: &Entity, perception: &Perception) -> Vec2 {
+        perception.prey_scent.normalize_or_zero()
+    }
+}
+
+/// Follows the scent of the entity's own species uphill, towards where
+/// others have been most.
+pub struct FollowTrail;
+
+impl Behaviour for FollowTrail {
+    fn steer(&self, _entity: &Entity, perception: &Perception) -> Vec2 {
+        perception.kin_scent.normalize_or_zero()
+    }
+}
+
+/// Heads away from where others of the species raised the alarm last tick,
+/// closer calls pushing harder.
+pub struct HeedAlarm;
+
+impl Behaviour for HeedAlarm {
+    fn steer(&self, entity: &Entity, perception: &Perception) -> Vec2 {
+        push(entity, perception.alarms.iter().copied())
+    }
+}
+
+/// Heads for where others of the species called out food last tick, closer
+/// calls pulling harder. Well-fed entities are pulled less.
+pub struct HeedCall;
+
+impl Behaviour for HeedCall {
+    fn steer(&self, entity: &Entity, perception: &Perception) -> Vec2 {
+        appetite(entity) * pull(entity, perception.calls.iter().copied())
+    }
+}
+
+/// Falls back on memory when nothing is in sight: heads for the food it
+/// last saw, or home with a pull of `home` if it remembers none, and away
+/// from the last threat as `Flee` would. Fading recollections pull less.
+pub struct Recall {
+    pub home: f32,
+}
+
+impl Behaviour for Recall {
+    fn steer(&self, entity: &Entity, perception: &Perception) -> Vec2 {
+        let memory = &perception.memory;
+        let mut wish = Vec2::ZERO;
+        if perception.targets.is_empty() {
+            wish += match memory.food {
+                Some(food) => food.strength * (food.pos - entity.pos).normalize_or_zero(),
+                None => self.home * (memory.home - entity.pos).normalize_or_zero(),
+            };
+        }
+        if let Some(threat) = memory.threat.filter(|_| perception.threats.is_empty()) {
+            wish += threat.strength * towards(threat.pos, entity.pos);
+        }
+        wish
+    }
+}
@@ -0,0 +1,130 @@
+use std::f32::consts::PI;
+
+use macroquad::prelude::*;
+
+use super::{angle_between, config::Config, Entity, Perception};
+
+/// Number of sensor readings fed into a brain.
+pub const INPUTS: usize = 6;
+/// Number of values a brain answers with: turn and speed.
+pub const OUTPUTS: usize = 2;
+
+/// A small fully connected feed-forward network with `tanh` activations.
+#[derive(Debug, Clone)]
+pub struct Brain {
+    /// Neurons per layer, inputs first and outputs last.
+    shape: Vec<usize>,
+    /// One matrix per layer after the inputs, row-major with a trailing bias
+    /// column, so each row holds `previous layer + 1` weights.
+    weights: Vec<Vec<f32>>,
+}
+
+impl Brain {
+    /// A network with `hidden` neurons between the sensors and the outputs,
+    /// every weight drawn uniformly from `[-1, 1]`.
+    pub fn random(hidden: &[usize]) -> Self {
+        let mut shape = vec![INPUTS];
+        shape.extend_from_slice(hidden);
+        shape.push(OUTPUTS);
+
+        let weights = shape
+            .windows(2)
+            .map(|pair| {
+                (0..pair[1] * (pair[0] + 1))
+                    .map(|_| rand::gen_range(-1.0, 1.0))
+                    .collect()
+            })
+            .collect();
+
+        Brain { shape, weights }
+    }
+
+    /// Runs `inputs` through the network.
+    pub fn think(&self, inputs: &[f32]) -> Vec<f32> {
+        let mut activations = inputs.to_vec();
+        for (layer, weights) in self.weights.iter().enumerate() {
+            let width = self.shape[layer] + 1;
+            activations = weights
+                .chunks(width)
+                .map(|row| {
+                    let sum: f32 = row.iter().zip(activations.iter()).map(|(w, a)| w * a).sum();
+                    (sum + row[width - 1]).tanh()
+                })
+                .collect();
+        }
+        activations
+    }
+
+    /// Each weight taken from either parent with equal chance. Parents of
+    /// different shapes can't be mixed, so the child copies `self`.
+    pub fn crossover(&self, other: &Brain) -> Self {
+        if self.shape != other.shape {
+            return self.clone();
+        }
+        Brain {
+            shape: self.shape.clone(),
+            weights: self
+                .weights
+                .iter()
+                .zip(other.weights.iter())
+                .map(|(ours, theirs)| {
+                    ours.iter()
+                        .zip(theirs.iter())
+                        .map(|(a, b)| if rand::gen_range(0, 2) == 0 { *a } else { *b })
+                        .collect()
+                })
+                .collect(),
+        }
+    }
+
+    /// A copy where each weight, with probability `rate`, is shifted by up to `strength`.
+    pub fn mutate(&self, rate: f32, strength: f32) -> Self {
+        Brain {
+            shape: self.shape.clone(),
+            weights: self
+                .weights
+                .iter()
+                .map(|layer| {
+                    layer
+                        .iter()
+                        .map(|w| {
+                            if rand::gen_range(0.0, 1.0) < rate {
+                                w + rand::gen_range(-strength, strength)
+                            } else {
+                                *w
+                            }
+                        })
+                        .collect()
+                })
+                .collect(),
+        }
+    }
+}
+
+/// What a brain gets to know each tick, every reading roughly in `[-1, 1]`.
+///
+/// Angles are relative to the heading and divided by `PI`; distances are
+/// divided by the vision range and read 1 when nothing is in sight.
+pub fn sensors(entity: &Entity, perception: &Perception, config: &Config) -> [f32; INPUTS] {
+    let bearing = |pos: Option<&Vec2>| match pos {
+        Some(pos) => {
+            let offset = *pos - entity.pos;
+            (
+                angle_between(entity.direction, offset.to_angle()) / PI,
+                (offset.length() / entity.genes.vision_range).min(1.0),
+            )
+        }
+        None => (0.0, 1.0),
+    };
+    let (target_angle, target_distance) = bearing(perception.targets.first());
+    let (threat_angle, threat_distance) = bearing(perception.threats.first().map(|e| &e.pos));
+
+    [
+        target_angle,
+        target_distance,
+        threat_angle,
+        threat_distance,
+        (entity.energy / config.reproduction_threshold).min(1.0),
+        (entity.speed / entity.max_speed()).min(1.0),
+    ]
+}
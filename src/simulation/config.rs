@@ -0,0 +1,206 @@
+use serde::{Deserialize, Serialize};
+
+use super::{
+    edges::Edges,
+    obstacle::Obstacle,
+    pheromone::PheromoneConfig,
+    species::{FoodWeb, Species},
+    terrain::TerrainConfig,
+};
+
+/// Which controller newly spawned entities get.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum ControllerKind {
+    Behaviours,
+    NeuralNet,
+    Neat,
+}
+
+/// How the population is renewed.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Mode {
+    /// Entities reproduce whenever they have enough energy.
+    Continuous,
+    /// Episodes of `generation_ticks` ticks; the next generation is bred from
+    /// the fittest of the last one.
+    Generational,
+}
+
+/// How an entity is scored at the end of a generation: the sum of each
+/// measure times its weight.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Fitness {
+    /// Per unit of nutrition eaten.
+    pub food: f32,
+    /// Per tick survived.
+    pub survival: f32,
+    /// Per unit of energy left at the end.
+    pub energy: f32,
+}
+
+impl Default for Fitness {
+    fn default() -> Self {
+        Fitness {
+            food: 1.0,
+            survival: 0.001,
+            energy: 0.0,
+        }
+    }
+}
+
+/// Tunable parameters of the simulation, loaded from a JSON file with
+/// `--config`. Missing fields keep their defaults; unknown ones, such as
+/// the per-role keys from before species were configurable, are an error.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Ticks a predator spends handling a kill before it can move or hunt again.
+    /// Zero lets it chase the next prey straight away.
+    pub handling_time: u32,
+    /// Nutrition a freshly spawned food item holds.
+    pub food_nutrition: f32,
+    /// Most nutrition an entity can take from a food item in one tick. Anything
+    /// below `food_nutrition` makes food last several ticks.
+    pub bite_size: f32,
+    /// Nutrition a carcass loses every tick as it rots.
+    pub carcass_decay: f32,
+    /// Fraction of a killed entity's nutrition the predator leaves on the
+    /// carcass instead of eating it. Starved entities leave all of it.
+    pub carcass_leftover: f32,
+    /// Energy every entity starts with.
+    pub initial_energy: f32,
+    /// Distance at which a threat makes an entity panic and sprint.
+    pub panic_radius: f32,
+    /// Speed multiplier while panicking.
+    pub panic_speed_boost: f32,
+    /// Extra energy spent for every tick of panic. Exhausted entities can't panic.
+    pub panic_energy_cost: f32,
+    /// Fraction of top speed entities settle at when nothing is in sight.
+    pub cruise_speed: f32,
+    /// Energy spent every tick just staying alive.
+    pub basal_metabolism: f32,
+    /// Energy spent per tick for moving, multiplied by speed squared.
+    pub movement_cost: f32,
+    /// Energy above which an entity gives some to hungrier relatives, and
+    /// below which it accepts gifts.
+    pub share_threshold: f32,
+    /// Energy handed over per tick by a donor whose `share` gene is 1 to a
+    /// clone of itself; less related pairs and less generous donors share less.
+    pub share_amount: f32,
+    /// How close a relative must be to be fed.
+    pub share_range: f32,
+    /// `coop` gene value from which predators hunt in packs: they settle on
+    /// the same prey as packmates nearby and split whatever they catch.
+    pub pack_coop: f32,
+    /// How close packmates must be to hunt together, and to the predator
+    /// that made a kill to get a share of it.
+    pub pack_range: f32,
+    /// How far a call carries.
+    pub signal_radius: f32,
+    /// Energy a call costs the caller.
+    pub signal_cost: f32,
+    /// How much of a recollection fades every tick; it is forgotten after
+    /// `1 / memory_decay` ticks without seeing the thing again.
+    pub memory_decay: f32,
+    /// Energy at which an entity splits off an offspring, handing it half.
+    pub reproduction_threshold: f32,
+    /// How far, as a fraction, each gene may drift between parent and offspring.
+    pub mutation_rate: f32,
+    /// Expected number of food items sprouting somewhere in the world each tick.
+    pub food_growth_rate: f32,
+    /// Controller given to entities spawned from scratch; offspring inherit their parent's.
+    pub controller: ControllerKind,
+    /// Neurons in each hidden layer of a new neural controller.
+    pub brain_hidden_layers: Vec<usize>,
+    /// Chance for each brain weight to change between parent and offspring.
+    pub brain_mutation_rate: f32,
+    /// Largest change to a mutated brain weight.
+    pub brain_mutation_strength: f32,
+    /// Chance for a NEAT offspring to gain a connection.
+    pub neat_add_connection_rate: f32,
+    /// Chance for a NEAT offspring to gain a hidden node.
+    pub neat_add_node_rate: f32,
+    /// Compatibility distance under which two NEAT genomes share a species.
+    pub neat_compatibility_threshold: f32,
+    /// Food items spawned when the world is (re)populated.
+    pub initial_food: usize,
+    pub mode: Mode,
+    /// Length of a generation in generational mode.
+    pub generation_ticks: u32,
+    /// Number of entities a parent is picked from by tournament selection.
+    pub tournament_size: usize,
+    pub fitness: Fitness,
+    /// The species living in the world; their diets make up the food web.
+    pub species: Vec<Species>,
+    /// Static shapes in the world, on top of any drawn with the mouse.
+    pub obstacles: Vec<Obstacle>,
+    pub terrain: TerrainConfig,
+    pub pheromones: PheromoneConfig,
+    pub edges: Edges,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            handling_time: 30,
+            food_nutrition: 1.0,
+            bite_size: 0.25,
+            carcass_decay: 0.005,
+            carcass_leftover: 0.5,
+            initial_energy: 5.0,
+            panic_radius: 40.0,
+            panic_speed_boost: 1.6,
+            panic_energy_cost: 0.02,
+            cruise_speed: 0.6,
+            basal_metabolism: 0.002,
+            movement_cost: 0.004,
+            share_threshold: 6.0,
+            share_amount: 0.02,
+            share_range: 15.0,
+            pack_coop: 0.7,
+            pack_range: 80.0,
+            signal_radius: 60.0,
+            signal_cost: 0.02,
+            memory_decay: 0.005,
+            reproduction_threshold: 10.0,
+            mutation_rate: 0.05,
+            food_growth_rate: 0.5,
+            controller: ControllerKind::Behaviours,
+            brain_hidden_layers: vec![8],
+            brain_mutation_rate: 0.1,
+            brain_mutation_strength: 0.5,
+            neat_add_connection_rate: 0.05,
+            neat_add_node_rate: 0.03,
+            neat_compatibility_threshold: 1.0,
+            initial_food: 500,
+            mode: Mode::Continuous,
+            generation_ticks: 2000,
+            tournament_size: 3,
+            fitness: Fitness::default(),
+            species: vec![Species::default_prey(), Species::default_predator()],
+            obstacles: vec![],
+            terrain: TerrainConfig::default(),
+            pheromones: PheromoneConfig::default(),
+            edges: Edges::Wrap,
+        }
+    }
+}
+
+impl Config {
+    pub fn load(path: &str) -> Result<Self, String> {
+        let json = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        let config: Config = serde_json::from_str(&json).map_err(|e| format!("{}: {}", path, e))?;
+        FoodWeb::new(&config.species).map_err(|e| format!("{}: {}", path, e))?;
+        for species in config.species.iter() {
+            species
+                .genes
+                .check()
+                .map_err(|e| format!("{}: {}: {}", path, species.name, e))?;
+        }
+        for obstacle in config.obstacles.iter() {
+            obstacle.check().map_err(|e| format!("{}: {}", path, e))?;
+        }
+        Ok(config)
+    }
+}
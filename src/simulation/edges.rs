@@ -0,0 +1,83 @@
+use std::f32::consts::PI;
+
+use macroquad::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// What happens at the edges of the world.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Edges {
+    /// The world is a torus: leaving one side enters the opposite one, and
+    /// entities see and reach across the seam.
+    Wrap,
+    /// Solid walls entities slide along.
+    Walls,
+    /// Solid walls entities bounce off, mirroring their heading.
+    Reflective,
+    /// Entities that step off the world are gone for good.
+    Absorbing,
+}
+
+impl Edges {
+    pub fn wraps(&self) -> bool {
+        *self == Edges::Wrap
+    }
+
+    /// Keeps a body that just moved to `pos` inside a world of `size`,
+    /// turning `direction` if it bounced. Absorbing edges leave `pos` outside,
+    /// which the caller treats as death.
+    pub fn contain(&self, pos: &mut Vec2, direction: &mut f32, size: Vec2) {
+        match self {
+            Edges::Wrap => {
+                pos.x = pos.x.rem_euclid(size.x);
+                pos.y = pos.y.rem_euclid(size.y);
+            }
+            Edges::Walls => *pos = clamp_inside(*pos, size),
+            Edges::Reflective => {
+                if pos.x < 0.0 || pos.x >= size.x {
+                    pos.x = if pos.x < 0.0 {
+                        -pos.x
+                    } else {
+                        2.0 * size.x - pos.x
+                    };
+                    *direction = PI - *direction;
+                }
+                if pos.y < 0.0 || pos.y >= size.y {
+                    pos.y = if pos.y < 0.0 {
+                        -pos.y
+                    } else {
+                        2.0 * size.y - pos.y
+                    };
+                    *direction = -*direction;
+                }
+                *pos = clamp_inside(*pos, size);
+            }
+            Edges::Absorbing => {}
+        }
+    }
+
+    /// The shortest way to cover `offset` between two points in a world of
+    /// `size`, which in a wrapping world may run across an edge.
+    pub fn wrapped_offset(&self, offset: Vec2, size: Vec2) -> Vec2 {
+        if !self.wraps() {
+            return offset;
+        }
+        let shortest = |d: f32, side: f32| (d + side / 2.0).rem_euclid(side) - side / 2.0;
+        Vec2::new(shortest(offset.x, size.x), shortest(offset.y, size.y))
+    }
+
+    /// Where something spawned at `pos` ends up, so it always starts inside.
+    pub fn place(&self, pos: Vec2, size: Vec2) -> Vec2 {
+        match self {
+            Edges::Wrap => Vec2::new(pos.x.rem_euclid(size.x), pos.y.rem_euclid(size.y)),
+            _ => clamp_inside(pos, size),
+        }
+    }
+}
+
+/// `pos` clamped to the world, whose right and bottom edges are exclusive.
+fn clamp_inside(pos: Vec2, size: Vec2) -> Vec2 {
+    Vec2::new(
+        pos.x.clamp(0.0, size.x - 0.01),
+        pos.y.clamp(0.0, size.y - 0.01),
+    )
+}
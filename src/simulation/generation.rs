@@ -0,0 +1,158 @@
+use std::collections::HashMap;
+
+use macroquad::prelude::*;
+
+use super::{kin::Sharing, mortality::Deaths, pack::Hunts, Controller, Entity, Mode, Simulation};
+
+/// Column names of the lines `Simulation::summary` prints.
+const SUMMARY_HEADER: &str = "generation,species,members,survivors,mean_fitness,best_fitness,\
+mean_food,mean_coop,mean_share,mean_max_speed,eaten,starved,old_age,lost,\
+shares,mean_share_relatedness,solo_kills,pack_kills,mean_alarm,mean_food_call,mean_false_call";
+
+impl Simulation {
+    /// Runs `generations` whole generations without a window and returns when
+    /// they are done. Summaries are printed to stdout as each one ends.
+    pub fn run_headless(&mut self, generations: u32) {
+        self.config.mode = Mode::Generational;
+        self.populate();
+        while self.generation < generations {
+            self.tick();
+        }
+    }
+
+    /// Ends the current generation: scores everyone who lived in it, prints a
+    /// summary and breeds a fresh population of the same size from the fittest,
+    /// NEAT genomes sharing their fitness with the rest of their NEAT species.
+    pub(super) fn next_generation(&mut self) {
+        let mut members = std::mem::take(&mut self.entities);
+        members.append(&mut self.fallen);
+
+        if self.generation == 0 {
+            println!("{}", SUMMARY_HEADER);
+        }
+
+        for species in 0..self.config.species.len() {
+            let mut scored: Vec<(f32, Entity)> = members
+                .iter()
+                .filter(|e| e.species == species)
+                .map(|e| (e.fitness(&self.config.fitness), e.clone()))
+                .collect();
+            if scored.is_empty() {
+                continue;
+            }
+            scored.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap());
+            println!("{}", self.summary(species, &scored));
+
+            share_fitness(&mut scored);
+            for _ in 0..scored.len() {
+                self.breed(&scored);
+            }
+        }
+        self.prune_neat_species();
+
+        self.deaths = vec![Deaths::default(); self.config.species.len()];
+        self.sharing = vec![Sharing::default(); self.config.species.len()];
+        self.hunts = vec![Hunts::default(); self.config.species.len()];
+        self.foods.clear();
+        self.seed_food();
+        self.generation += 1;
+        self.generation_tick = 0;
+    }
+
+    /// Picks two parents by tournament, crosses and mutates them, and drops
+    /// the child somewhere in the world with a full starting energy.
+    fn breed(&mut self, scored: &[(f32, Entity)]) {
+        let (a, b) = (self.select(scored), self.select(scored));
+        let (fitter, other) = if a.0 >= b.0 { (a, b) } else { (b, a) };
+        let (fitter, other) = (&fitter.1, &other.1);
+
+        let genes = fitter
+            .genes
+            .crossover(&other.genes)
+            .mutate(self.config.mutation_rate);
+        let mut controller = fitter
+            .controller
+            .crossover(&other.controller)
+            .mutate(&self.config, &mut self.innovations);
+        if let Controller::Neat(genome) = &mut controller {
+            genome.species = self
+                .neat_species
+                .assign(genome, self.config.neat_compatibility_threshold);
+        }
+
+        let id = self.next_id();
+        let pos = self.random_spot();
+        self.entities.push(Entity::new(
+            id,
+            pos,
+            fitter.species,
+            &self.config.species[fitter.species],
+            genes,
+            controller,
+            self.config.initial_energy,
+        ));
+    }
+
+    /// The fittest of `tournament_size` members drawn at random.
+    fn select<'a>(&self, scored: &'a [(f32, Entity)]) -> &'a (f32, Entity) {
+        (0..self.config.tournament_size.max(1))
+            .map(|_| &scored[rand::gen_range(0, scored.len())])
+            .max_by(|a, b| a.0.partial_cmp(&b.0).unwrap())
+            .unwrap()
+    }
+
+    /// One CSV line per species and generation; `scored` is sorted best first.
+    fn summary(&self, species: usize, scored: &[(f32, Entity)]) -> String {
+        let n = scored.len() as f32;
+        let mean = |f: fn(&Entity) -> f32| scored.iter().map(|(_, e)| f(e)).sum::<f32>() / n;
+        let survivors = scored
+            .iter()
+            .filter(|(_, e)| e.death(self.boundary).is_none())
+            .count();
+        let deaths = &self.deaths[species];
+        let sharing = &self.sharing[species];
+        let hunts = &self.hunts[species];
+
+        format!(
+            "{},{},{},{},{:.3},{:.3},{:.3},{:.3},{:.3},{:.3},{},{},{},{},{},{:.3},{},{},{:.3},{:.3},{:.3}",
+            self.generation,
+            self.config.species[species].name,
+            scored.len(),
+            survivors,
+            scored.iter().map(|(f, _)| f).sum::<f32>() / n,
+            scored[0].0,
+            mean(|e| e.food_collected),
+            mean(|e| e.genes.coop),
+            mean(|e| e.genes.share),
+            mean(|e| e.genes.max_speed),
+            deaths.eaten,
+            deaths.starved,
+            deaths.old_age,
+            deaths.lost,
+            sharing.shares,
+            sharing.mean_relatedness(),
+            hunts.solo,
+            hunts.pack,
+            mean(|e| e.genes.alarm),
+            mean(|e| e.genes.food_call),
+            mean(|e| e.genes.false_call),
+        )
+    }
+}
+
+/// Explicit fitness sharing: divides the score of every NEAT genome by how
+/// many members of `scored` share its NEAT species, so a new structure isn't
+/// outbred by a crowded species before its weights have had time to improve.
+fn share_fitness(scored: &mut [(f32, Entity)]) {
+    let mut sizes: HashMap<usize, usize> = HashMap::new();
+    for (_, entity) in scored.iter() {
+        if let Controller::Neat(genome) = &entity.controller {
+            *sizes.entry(genome.species).or_default() += 1;
+        }
+    }
+    for (fitness, entity) in scored.iter_mut() {
+        if let Controller::Neat(genome) = &entity.controller {
+            *fitness /= sizes[&genome.species] as f32;
+        }
+    }
+}
@@ -0,0 +1,173 @@
+use std::f32::consts::PI;
+
+use macroquad::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Heritable traits, passed on to offspring with a little mutation.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Genes {
+    pub coop: f32,
+    pub share: f32,
+    /// Top speed in pixels per tick.
+    pub max_speed: f32,
+    /// Largest heading change per tick, in radians.
+    pub max_turn_rate: f32,
+    /// Largest speed change per tick.
+    pub max_acceleration: f32,
+    /// How far the entity can see inside its vision cone.
+    pub vision_range: f32,
+    /// Full width of the vision cone in radians, centred on the heading.
+    pub vision_angle: f32,
+    /// Radius all around the entity, behind it included, in which it notices things.
+    pub hearing_range: f32,
+    /// Chance per tick of raising the alarm while a threat is in sight.
+    #[serde(default)]
+    pub alarm: f32,
+    /// Chance per tick of calling others over while food is in sight.
+    #[serde(default)]
+    pub food_call: f32,
+    /// Chance per tick of making a call with nothing to back it: an alarm
+    /// with no threat in sight or a food call with no food in sight.
+    #[serde(default)]
+    pub false_call: f32,
+    /// Neutral marker bits that do nothing themselves but are inherited like
+    /// everything else, so the share of bits two entities have in common
+    /// tells how closely related they are.
+    #[serde(skip)]
+    pub kin: [u64; KIN_WORDS],
+}
+
+/// Words of kin marker bits. With 2048 bits, strangers' relatedness scatters
+/// by about 0.02 around 0, well below that of first cousins at 0.125.
+const KIN_WORDS: usize = 32;
+
+impl Genes {
+    /// A founder of a species: every gene from `template`, except `coop` and
+    /// `share`, drawn at random if `random_social` is set, and a lineage of
+    /// its own.
+    pub fn founder(template: &Genes, random_social: bool) -> Self {
+        let social = |gene: f32| {
+            if random_social {
+                rand::gen_range(0.0, 1.0)
+            } else {
+                gene
+            }
+        };
+        Genes {
+            coop: social(template.coop),
+            share: social(template.share),
+            kin: std::array::from_fn(|_| random_bits()),
+            ..template.clone()
+        }
+    }
+
+    /// Each gene taken from either parent with equal chance.
+    pub fn crossover(&self, other: &Genes) -> Self {
+        Genes {
+            coop: pick(self.coop, other.coop),
+            share: pick(self.share, other.share),
+            max_speed: pick(self.max_speed, other.max_speed),
+            max_turn_rate: pick(self.max_turn_rate, other.max_turn_rate),
+            max_acceleration: pick(self.max_acceleration, other.max_acceleration),
+            vision_range: pick(self.vision_range, other.vision_range),
+            vision_angle: pick(self.vision_angle, other.vision_angle),
+            hearing_range: pick(self.hearing_range, other.hearing_range),
+            alarm: pick(self.alarm, other.alarm),
+            food_call: pick(self.food_call, other.food_call),
+            false_call: pick(self.false_call, other.false_call),
+            kin: std::array::from_fn(|i| {
+                let mask = random_bits();
+                (self.kin[i] & mask) | (other.kin[i] & !mask)
+            }),
+        }
+    }
+
+    /// Why the genes as written in a config file make no sense, if they
+    /// don't: every gene must lie in the range mutation keeps it in.
+    pub fn check(&self) -> Result<(), String> {
+        let ranges = [
+            ("coop", self.coop, 0.0, 1.0),
+            ("share", self.share, 0.0, 1.0),
+            ("max_speed", self.max_speed, 0.1, f32::INFINITY),
+            ("max_turn_rate", self.max_turn_rate, 0.01, f32::INFINITY),
+            (
+                "max_acceleration",
+                self.max_acceleration,
+                0.001,
+                f32::INFINITY,
+            ),
+            ("vision_range", self.vision_range, 0.0, f32::INFINITY),
+            ("vision_angle", self.vision_angle, 0.0, 2.0 * PI),
+            ("hearing_range", self.hearing_range, 0.0, f32::INFINITY),
+            ("alarm", self.alarm, 0.0, 1.0),
+            ("food_call", self.food_call, 0.0, 1.0),
+            ("false_call", self.false_call, 0.0, 1.0),
+        ];
+        for (name, value, min, max) in ranges {
+            if (min..=max).contains(&value) {
+                continue;
+            }
+            return Err(if max.is_finite() {
+                format!(
+                    "{} must be between {} and {}, got {}",
+                    name, min, max, value
+                )
+            } else {
+                format!("{} must be at least {}, got {}", name, min, value)
+            });
+        }
+        Ok(())
+    }
+
+    /// A copy with every gene nudged by up to `rate` of its own value.
+    pub fn mutate(&self, rate: f32) -> Self {
+        Genes {
+            coop: nudge(self.coop, rate).clamp(0.0, 1.0),
+            share: nudge(self.share, rate).clamp(0.0, 1.0),
+            max_speed: nudge(self.max_speed, rate).max(0.1),
+            max_turn_rate: nudge(self.max_turn_rate, rate).max(0.01),
+            max_acceleration: nudge(self.max_acceleration, rate).max(0.001),
+            vision_range: nudge(self.vision_range, rate).max(0.0),
+            vision_angle: nudge(self.vision_angle, rate).clamp(0.0, 2.0 * PI),
+            hearing_range: nudge(self.hearing_range, rate).max(0.0),
+            alarm: nudge(self.alarm, rate).clamp(0.0, 1.0),
+            food_call: nudge(self.food_call, rate).clamp(0.0, 1.0),
+            false_call: nudge(self.false_call, rate).clamp(0.0, 1.0),
+            kin: self.kin.map(|word| {
+                (0..64)
+                    .filter(|_| rand::gen_range(0.0, 1.0) < rate)
+                    .fold(word, |word, bit| word ^ (1 << bit))
+            }),
+        }
+    }
+
+    /// Estimated relatedness to `other`, from 0 for strangers, who share half
+    /// their kin bits by chance, to 1 for clones. A parent and its child by
+    /// crossover come out at about a half.
+    pub fn relatedness(&self, other: &Genes) -> f32 {
+        let differing: u32 = self
+            .kin
+            .iter()
+            .zip(other.kin.iter())
+            .map(|(a, b)| (a ^ b).count_ones())
+            .sum();
+        (1.0 - 2.0 * differing as f32 / (64 * KIN_WORDS) as f32).max(0.0)
+    }
+}
+
+fn random_bits() -> u64 {
+    ((rand::rand() as u64) << 32) | rand::rand() as u64
+}
+
+fn pick(a: f32, b: f32) -> f32 {
+    if rand::gen_range(0, 2) == 0 {
+        a
+    } else {
+        b
+    }
+}
+
+fn nudge(value: f32, rate: f32) -> f32 {
+    value * (1.0 + rand::gen_range(-rate, rate))
+}
@@ -0,0 +1,62 @@
+use std::collections::HashSet;
+
+use super::{Claim, Simulation};
+
+/// Energy handed between entities of a species, and how related the pairs were.
+#[derive(Debug, Clone, Default)]
+pub struct Sharing {
+    pub shares: u32,
+    /// Sum of the relatedness of every sharing pair.
+    pub relatedness: f32,
+}
+
+impl Sharing {
+    pub fn record(&mut self, relatedness: f32) {
+        self.shares += 1;
+        self.relatedness += relatedness;
+    }
+
+    /// Average relatedness of donor and recipient, or 0 if nobody shared.
+    pub fn mean_relatedness(&self) -> f32 {
+        if self.shares == 0 {
+            0.0
+        } else {
+            self.relatedness / self.shares as f32
+        }
+    }
+}
+
+impl Simulation {
+    /// Entities with energy above `share_threshold` offering some to a
+    /// hungrier relative of their species within `share_range`. Nobody who
+    /// was just caught gives or receives.
+    pub(super) fn share_claims(&self, caught: &HashSet<u64>) -> Vec<Claim> {
+        let mut claims = vec![];
+        for donor in self.entities.iter().filter(|e| {
+            e.energy > self.config.share_threshold && e.genes.share > 0.0 && !caught.contains(&e.id)
+        }) {
+            for recipient in self
+                .entity_qt
+                .query_around(donor.pos, self.config.share_range)
+                .into_iter()
+                .filter(|e| {
+                    e.id != donor.id
+                        && e.species == donor.species
+                        && e.energy < self.config.share_threshold
+                        && !caught.contains(&e.id)
+                        && donor.genes.relatedness(&e.genes) > 0.0
+                })
+            {
+                let distance = donor.pos.distance(recipient.pos);
+                if distance <= self.config.share_range {
+                    claims.push(Claim {
+                        eater: donor.id,
+                        target: recipient.id,
+                        distance,
+                    });
+                }
+            }
+        }
+        claims
+    }
+}
@@ -0,0 +1,172 @@
+use std::collections::{HashMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+
+use super::{Controller, Entity, Simulation};
+
+/// How a species makes offspring in continuous mode.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Reproduction {
+    /// An entity with enough energy splits off a mutated copy of itself.
+    Asexual,
+    /// Two ready entities of the species court for a while, then have a
+    /// child crossing both parents.
+    Sexual,
+}
+
+/// A pair standing still together until their child is born.
+#[derive(Debug, Clone, Copy)]
+pub struct Courtship {
+    pub partner: u64,
+    pub ticks_left: u32,
+}
+
+impl Simulation {
+    /// Whether `entity` belongs to a sexual species and could start courting.
+    fn ready_to_mate(&self, entity: &Entity) -> bool {
+        self.config.species[entity.species].reproduction == Reproduction::Sexual
+            && !entity.is_juvenile()
+            && entity.energy >= self.config.reproduction_threshold
+            && entity.cooldown == 0
+            && entity.courtship.is_none()
+    }
+
+    /// Births children of finished courtships, pairs up ready entities in
+    /// range of each other, and flags who is still looking for a mate.
+    pub(super) fn mate(&mut self) -> Vec<Entity> {
+        let offspring = self.finish_courtships();
+        self.start_courtships();
+        for i in 0..self.entities.len() {
+            self.entities[i].ready_to_mate = self.ready_to_mate(&self.entities[i]);
+        }
+        offspring
+    }
+
+    fn finish_courtships(&mut self) -> Vec<Entity> {
+        let index: HashMap<u64, usize> = self
+            .entities
+            .iter()
+            .enumerate()
+            .map(|(i, e)| (e.id, i))
+            .collect();
+
+        let mut offspring = vec![];
+        for i in 0..self.entities.len() {
+            let Some(courtship) = self.entities[i].courtship else {
+                continue;
+            };
+            let partner = index.get(&courtship.partner).copied();
+            // A partner that died or was eaten ends the courtship.
+            let Some(j) = partner else {
+                self.entities[i].courtship = None;
+                continue;
+            };
+            if courtship.ticks_left > 0 || self.entities[i].id > courtship.partner {
+                continue;
+            }
+
+            let id = self.next_id();
+            offspring.push(self.breed_pair(id, i, j));
+            let cooldown = self.config.species[self.entities[i].species].mating_cooldown;
+            for parent in [i, j] {
+                self.entities[parent].courtship = None;
+                self.entities[parent].cooldown = cooldown;
+            }
+        }
+        offspring
+    }
+
+    /// The child of entities `i` and `j`. Each parent hands it a quarter of
+    /// its energy; the one with more energy left counts as the fitter parent.
+    fn breed_pair(&mut self, id: u64, i: usize, j: usize) -> Entity {
+        let (fitter, other) = if self.entities[i].energy >= self.entities[j].energy {
+            (i, j)
+        } else {
+            (j, i)
+        };
+        let (a, b) = (&self.entities[fitter], &self.entities[other]);
+
+        let genes = a
+            .genes
+            .crossover(&b.genes)
+            .mutate(self.config.mutation_rate);
+        let mut controller = a
+            .controller
+            .crossover(&b.controller)
+            .mutate(&self.config, &mut self.innovations);
+        if let Controller::Neat(genome) = &mut controller {
+            genome.species = self
+                .neat_species
+                .assign(genome, self.config.neat_compatibility_threshold);
+        }
+        let size = self.boundary.size();
+        let pos = a.pos + self.config.edges.wrapped_offset(b.pos - a.pos, size) / 2.0;
+        let species = a.species;
+
+        let mut energy = 0.0;
+        for parent in [i, j] {
+            let gift = self.entities[parent].energy / 4.0;
+            self.entities[parent].energy -= gift;
+            energy += gift;
+        }
+
+        Entity::new(
+            id,
+            self.config.edges.place(pos, size),
+            species,
+            &self.config.species[species],
+            genes,
+            controller,
+            energy,
+        )
+    }
+
+    /// Pairs ready entities within `mating_range` of each other, closest
+    /// pairs first, and starts their courtship.
+    fn start_courtships(&mut self) {
+        let ready: HashMap<u64, usize> = self
+            .entities
+            .iter()
+            .enumerate()
+            .filter(|(_, e)| self.ready_to_mate(e))
+            .map(|(i, e)| (e.id, i))
+            .collect();
+
+        let mut pairs = vec![];
+        for &i in ready.values() {
+            let a = &self.entities[i];
+            let range = self.config.species[a.species].mating_range;
+            for b in self.entity_qt.query_around(a.pos, range) {
+                if a.id < b.id
+                    && b.species == a.species
+                    && ready.contains_key(&b.id)
+                    && a.pos.distance(b.pos) <= range
+                {
+                    pairs.push((a.pos.distance(b.pos), a.id, b.id));
+                }
+            }
+        }
+        pairs.sort_by(|x, y| {
+            x.0.partial_cmp(&y.0)
+                .unwrap()
+                .then(x.1.cmp(&y.1))
+                .then(x.2.cmp(&y.2))
+        });
+
+        let mut taken = HashSet::new();
+        for (_, a, b) in pairs {
+            if taken.contains(&a) || taken.contains(&b) {
+                continue;
+            }
+            taken.insert(a);
+            taken.insert(b);
+            let ticks = self.config.species[self.entities[ready[&a]].species].courtship_time;
+            for (me, partner) in [(a, b), (b, a)] {
+                self.entities[ready[&me]].courtship = Some(Courtship {
+                    partner,
+                    ticks_left: ticks,
+                });
+            }
+        }
+    }
+}
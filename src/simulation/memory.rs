@@ -0,0 +1,119 @@
+use macroquad::prelude::*;
+
+use super::{edges::Edges, Config, Entity, Perception, Simulation, FOOD_SIZE};
+
+/// Where the entity last saw something, and how sure it still is about it:
+/// 1 when just seen, fading to 0 when forgotten.
+#[derive(Debug, Clone, Copy)]
+pub struct Recollection {
+    pub pos: Vec2,
+    pub strength: f32,
+}
+
+impl Recollection {
+    fn fresh(pos: Vec2) -> Self {
+        Recollection { pos, strength: 1.0 }
+    }
+
+    /// The same recollection a tick later, or `None` once it has faded away.
+    fn fade(self, decay: f32) -> Option<Self> {
+        let strength = self.strength - decay;
+        (strength > 0.0).then_some(Recollection { strength, ..self })
+    }
+}
+
+/// What an entity keeps in mind between ticks. Places are kept inside the
+/// world, so in a wrapping world they may lie nearer across an edge.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Memory {
+    /// The nearest food it saw last.
+    pub food: Option<Recollection>,
+    /// The nearest threat it saw last.
+    pub threat: Option<Recollection>,
+    /// Where it was born.
+    pub home: Vec2,
+}
+
+impl Memory {
+    pub fn new(home: Vec2) -> Self {
+        Memory {
+            food: None,
+            threat: None,
+            home,
+        }
+    }
+}
+
+impl Memory {
+    /// The same memory with every place moved to where `along` takes it.
+    fn map(self, along: impl Fn(Vec2) -> Vec2) -> Memory {
+        let moved = |r: Recollection| Recollection {
+            pos: along(r.pos),
+            ..r
+        };
+        Memory {
+            food: self.food.map(moved),
+            threat: self.threat.map(moved),
+            home: along(self.home),
+        }
+    }
+}
+
+impl Entity {
+    /// Fills in what `perception` leaves out of the entity's memory: every
+    /// place it remembers, moved to its copy nearest the entity in a world of
+    /// `size`, so heading for it takes the shortest way.
+    pub(super) fn remember(&self, edges: Edges, size: Vec2, perception: &mut Perception) {
+        perception.memory = self
+            .memory
+            .map(|pos| self.pos + edges.wrapped_offset(pos - self.pos, size));
+    }
+
+    /// The entity's memory after this tick's `perception`, in a world of
+    /// `size`: whatever is in sight now replaces what it remembered, and the
+    /// rest fades by `memory_decay`. Remembered food that turns out to be
+    /// gone on arrival is forgotten.
+    pub(super) fn recall(&self, perception: &Perception, config: &Config, size: Vec2) -> Memory {
+        let arrived = |r: &Recollection| self.pos.distance(r.pos) <= self.size + FOOD_SIZE;
+        let memory = &perception.memory;
+        Memory {
+            food: perception.targets.first().map_or_else(
+                || {
+                    memory
+                        .food
+                        .and_then(|r| r.fade(config.memory_decay))
+                        .filter(|r| !arrived(r))
+                },
+                |p| Some(Recollection::fresh(*p)),
+            ),
+            threat: perception.threats.first().map_or_else(
+                || memory.threat.and_then(|r| r.fade(config.memory_decay)),
+                |t| Some(Recollection::fresh(t.pos)),
+            ),
+            home: memory.home,
+        }
+        .map(|pos| config.edges.place(pos, size))
+    }
+}
+
+impl Simulation {
+    /// A line from every entity to each thing it remembers, fading with the
+    /// recollection.
+    pub(super) fn draw_memories(&self) {
+        let size = self.boundary.size();
+        for entity in self.entities.iter() {
+            let memory = entity.memory;
+            for (recollection, colour) in [(memory.food, ORANGE), (memory.threat, RED)] {
+                if let Some(r) = recollection {
+                    let to =
+                        entity.pos + self.config.edges.wrapped_offset(r.pos - entity.pos, size);
+                    let colour = Color {
+                        a: 0.5 * r.strength,
+                        ..colour
+                    };
+                    draw_line(entity.pos.x, entity.pos.y, to.x, to.y, 1.0, colour);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,75 @@
+use macroquad::prelude::*;
+
+use super::{species::Species, Entity, Simulation};
+
+/// Why an entity left the world.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Death {
+    Eaten,
+    Starved,
+    OldAge,
+    /// Stepped over an absorbing edge.
+    Lost,
+}
+
+/// How many entities of a species died of each cause.
+#[derive(Debug, Clone, Default)]
+pub struct Deaths {
+    pub eaten: u32,
+    pub starved: u32,
+    pub old_age: u32,
+    pub lost: u32,
+}
+
+impl Deaths {
+    pub fn record(&mut self, death: Death) {
+        match death {
+            Death::Eaten => self.eaten += 1,
+            Death::Starved => self.starved += 1,
+            Death::OldAge => self.old_age += 1,
+            Death::Lost => self.lost += 1,
+        }
+    }
+}
+
+impl Entity {
+    /// Why the entity is dead, or `None` while it's alive.
+    pub(super) fn death(&self, boundary: Rect) -> Option<Death> {
+        if self.is_eaten {
+            Some(Death::Eaten)
+        } else if !boundary.contains(self.pos) {
+            Some(Death::Lost)
+        } else if self.energy <= 0.0 {
+            Some(Death::Starved)
+        } else if self.expired {
+            Some(Death::OldAge)
+        } else {
+            None
+        }
+    }
+}
+
+/// Chance that an entity of `species` aged `age` dies of old age this tick.
+/// It rises with the cube of age until it reaches `senescence` at the end of
+/// the lifespan, and no one outlives it.
+fn hazard(age: u32, species: &Species) -> f32 {
+    if species.lifespan == 0 {
+        return 0.0;
+    }
+    if age >= species.lifespan {
+        return 1.0;
+    }
+    species.senescence * (age as f32 / species.lifespan as f32).powi(3)
+}
+
+impl Simulation {
+    /// Rolls for every entity that wasn't eaten this tick whether old age
+    /// claims it. Runs after eats are settled, so each death has one cause.
+    pub(super) fn senesce(&mut self) {
+        for entity in self.entities.iter_mut() {
+            let species = &self.config.species[entity.species];
+            entity.expired =
+                !entity.is_eaten && rand::gen_range(0.0, 1.0) < hazard(entity.age, species);
+        }
+    }
+}
@@ -0,0 +1,510 @@
+use std::collections::{HashMap, HashSet};
+
+use macroquad::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    brain::{INPUTS, OUTPUTS},
+    config::Config,
+};
+
+/// Weights of excess genes, disjoint genes and average weight difference in
+/// the compatibility distance.
+const EXCESS_COEFFICIENT: f32 = 1.0;
+const DISJOINT_COEFFICIENT: f32 = 1.0;
+const WEIGHT_COEFFICIENT: f32 = 0.4;
+
+/// Node id of the always-on bias input; sensors use `0..INPUTS`.
+const BIAS: usize = INPUTS;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum NodeKind {
+    Input,
+    Bias,
+    Hidden,
+    Output,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NodeGene {
+    pub id: usize,
+    pub kind: NodeKind,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConnectionGene {
+    pub innovation: usize,
+    pub from: usize,
+    pub to: usize,
+    pub weight: f32,
+    pub enabled: bool,
+}
+
+/// A NEAT genome: a network whose topology grows through mutation.
+///
+/// Networks are kept acyclic so a single pass in topological order evaluates them.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Genome {
+    pub nodes: Vec<NodeGene>,
+    pub connections: Vec<ConnectionGene>,
+    /// The NEAT species this genome was placed in.
+    pub species: usize,
+}
+
+/// Hands out innovation numbers and node ids, so the same structural mutation
+/// gets the same numbers in every genome that makes it.
+#[derive(Debug)]
+pub struct Innovations {
+    next_innovation: usize,
+    next_node: usize,
+    connections: HashMap<(usize, usize), usize>,
+    splits: HashMap<usize, usize>,
+}
+
+impl Default for Innovations {
+    fn default() -> Self {
+        // Founders are fully connected with fixed numbers, see `Genome::minimal`.
+        Innovations {
+            next_innovation: (INPUTS + 1) * OUTPUTS,
+            next_node: INPUTS + 1 + OUTPUTS,
+            connections: HashMap::new(),
+            splits: HashMap::new(),
+        }
+    }
+}
+
+impl Innovations {
+    fn connection(&mut self, from: usize, to: usize) -> usize {
+        *self.connections.entry((from, to)).or_insert_with(|| {
+            self.next_innovation += 1;
+            self.next_innovation - 1
+        })
+    }
+
+    fn split(&mut self, innovation: usize) -> usize {
+        *self.splits.entry(innovation).or_insert_with(|| {
+            self.next_node += 1;
+            self.next_node - 1
+        })
+    }
+
+    fn fresh_node(&mut self) -> usize {
+        self.next_node += 1;
+        self.next_node - 1
+    }
+}
+
+impl Genome {
+    /// Every input and the bias wired straight to every output with random weights.
+    pub fn minimal() -> Self {
+        let mut nodes: Vec<NodeGene> = (0..INPUTS)
+            .map(|id| NodeGene {
+                id,
+                kind: NodeKind::Input,
+            })
+            .collect();
+        nodes.push(NodeGene {
+            id: BIAS,
+            kind: NodeKind::Bias,
+        });
+        nodes.extend((0..OUTPUTS).map(|i| NodeGene {
+            id: BIAS + 1 + i,
+            kind: NodeKind::Output,
+        }));
+
+        let connections = (0..=INPUTS)
+            .flat_map(|from| {
+                (0..OUTPUTS).map(move |i| ConnectionGene {
+                    innovation: from * OUTPUTS + i,
+                    from,
+                    to: BIAS + 1 + i,
+                    weight: rand::gen_range(-1.0, 1.0),
+                    enabled: true,
+                })
+            })
+            .collect();
+
+        Genome {
+            nodes,
+            connections,
+            species: 0,
+        }
+    }
+
+    /// Runs `inputs` through the network and returns the output nodes in order.
+    pub fn think(&self, inputs: &[f32]) -> Vec<f32> {
+        let mut values: HashMap<usize, f32> = HashMap::new();
+        for node in self.nodes.iter() {
+            match node.kind {
+                NodeKind::Input => {
+                    values.insert(node.id, inputs[node.id]);
+                }
+                NodeKind::Bias => {
+                    values.insert(node.id, 1.0);
+                }
+                _ => {}
+            }
+        }
+
+        for id in self.order() {
+            let kind = self.kind(id);
+            if kind == NodeKind::Input || kind == NodeKind::Bias {
+                continue;
+            }
+            let sum: f32 = self
+                .connections
+                .iter()
+                .filter(|c| c.enabled && c.to == id)
+                .map(|c| c.weight * values.get(&c.from).copied().unwrap_or(0.0))
+                .sum();
+            values.insert(id, sum.tanh());
+        }
+
+        self.nodes
+            .iter()
+            .filter(|n| n.kind == NodeKind::Output)
+            .map(|n| values[&n.id])
+            .collect()
+    }
+
+    fn kind(&self, id: usize) -> NodeKind {
+        self.nodes.iter().find(|n| n.id == id).unwrap().kind
+    }
+
+    /// Node ids in topological order of the enabled connections.
+    fn order(&self) -> Vec<usize> {
+        let mut incoming: HashMap<usize, usize> = self.nodes.iter().map(|n| (n.id, 0)).collect();
+        for c in self.connections.iter().filter(|c| c.enabled) {
+            *incoming.get_mut(&c.to).unwrap() += 1;
+        }
+
+        let mut ready: Vec<usize> = self
+            .nodes
+            .iter()
+            .map(|n| n.id)
+            .filter(|id| incoming[id] == 0)
+            .collect();
+        let mut order = vec![];
+        while let Some(id) = ready.pop() {
+            order.push(id);
+            for c in self
+                .connections
+                .iter()
+                .filter(|c| c.enabled && c.from == id)
+            {
+                let count = incoming.get_mut(&c.to).unwrap();
+                *count -= 1;
+                if *count == 0 {
+                    ready.push(c.to);
+                }
+            }
+        }
+        order
+    }
+
+    /// How many connections deep each node sits, inputs at zero. Outputs are
+    /// pushed to the deepest layer so they line up when drawn.
+    pub fn depths(&self) -> HashMap<usize, usize> {
+        let mut depths: HashMap<usize, usize> = HashMap::new();
+        for id in self.order() {
+            let depth = self
+                .connections
+                .iter()
+                .filter(|c| c.enabled && c.to == id)
+                .map(|c| depths.get(&c.from).copied().unwrap_or(0) + 1)
+                .max()
+                .unwrap_or(0);
+            depths.insert(id, depth);
+        }
+
+        let deepest = depths.values().copied().max().unwrap_or(0).max(1);
+        for node in self.nodes.iter().filter(|n| n.kind == NodeKind::Output) {
+            depths.insert(node.id, deepest);
+        }
+        depths
+    }
+
+    /// Draws the network inside `area`: sensors on the left, outputs on the
+    /// right and hidden nodes by depth. Green connections excite, red ones
+    /// inhibit, and thicker ones weigh more.
+    pub fn draw(&self, area: Rect) {
+        let depths = self.depths();
+        let deepest = depths.values().copied().max().unwrap_or(1) as f32;
+
+        let mut layers: HashMap<usize, Vec<usize>> = HashMap::new();
+        for node in self.nodes.iter() {
+            layers.entry(depths[&node.id]).or_default().push(node.id);
+        }
+        let mut positions: HashMap<usize, Vec2> = HashMap::new();
+        for (depth, ids) in layers.iter() {
+            for (i, id) in ids.iter().enumerate() {
+                positions.insert(
+                    *id,
+                    vec2(
+                        area.x + area.w * *depth as f32 / deepest,
+                        area.y + area.h * (i as f32 + 0.5) / ids.len() as f32,
+                    ),
+                );
+            }
+        }
+
+        for c in self.connections.iter().filter(|c| c.enabled) {
+            let (from, to) = (positions[&c.from], positions[&c.to]);
+            let colour = if c.weight > 0.0 { GREEN } else { RED };
+            draw_line(
+                from.x,
+                from.y,
+                to.x,
+                to.y,
+                c.weight.abs().clamp(0.5, 3.0),
+                colour,
+            );
+        }
+        for node in self.nodes.iter() {
+            let pos = positions[&node.id];
+            let colour = match node.kind {
+                NodeKind::Input => SKYBLUE,
+                NodeKind::Bias => GRAY,
+                NodeKind::Hidden => WHITE,
+                NodeKind::Output => ORANGE,
+            };
+            draw_circle(pos.x, pos.y, 4.0, colour);
+        }
+    }
+
+    /// Whether `to` can be reached from `from` along any connection.
+    fn reaches(&self, from: usize, to: usize) -> bool {
+        let mut seen = HashSet::new();
+        let mut stack = vec![from];
+        while let Some(id) = stack.pop() {
+            if id == to {
+                return true;
+            }
+            if seen.insert(id) {
+                stack.extend(
+                    self.connections
+                        .iter()
+                        .filter(|c| c.from == id)
+                        .map(|c| c.to),
+                );
+            }
+        }
+        false
+    }
+
+    /// NEAT crossover with `self` as the fitter parent: matching genes come
+    /// from either parent, disjoint and excess genes from `self` only. A gene
+    /// disabled in either parent stays disabled three times out of four.
+    pub fn crossover(&self, other: &Genome) -> Self {
+        let theirs: HashMap<usize, &ConnectionGene> = other
+            .connections
+            .iter()
+            .map(|c| (c.innovation, c))
+            .collect();
+
+        let connections = self
+            .connections
+            .iter()
+            .map(|ours| match theirs.get(&ours.innovation) {
+                Some(theirs) => {
+                    let mut gene = if rand::gen_range(0, 2) == 0 {
+                        ours.clone()
+                    } else {
+                        (*theirs).clone()
+                    };
+                    gene.enabled =
+                        (ours.enabled && theirs.enabled) || rand::gen_range(0.0, 1.0) < 0.25;
+                    gene
+                }
+                None => ours.clone(),
+            })
+            .collect();
+
+        Genome {
+            nodes: self.nodes.clone(),
+            connections,
+            species: self.species,
+        }
+    }
+
+    /// A copy with perturbed weights and, now and then, a new connection or node.
+    pub fn mutate(&self, config: &Config, innovations: &mut Innovations) -> Self {
+        let mut child = self.clone();
+
+        for connection in child.connections.iter_mut() {
+            if rand::gen_range(0.0, 1.0) < config.brain_mutation_rate {
+                connection.weight += rand::gen_range(
+                    -config.brain_mutation_strength,
+                    config.brain_mutation_strength,
+                );
+            }
+        }
+        if rand::gen_range(0.0, 1.0) < config.neat_add_connection_rate {
+            child.add_connection(innovations);
+        }
+        if rand::gen_range(0.0, 1.0) < config.neat_add_node_rate {
+            child.add_node(innovations);
+        }
+
+        child
+    }
+
+    fn add_connection(&mut self, innovations: &mut Innovations) {
+        for _ in 0..20 {
+            let from = self.nodes[rand::gen_range(0, self.nodes.len())].clone();
+            let to = self.nodes[rand::gen_range(0, self.nodes.len())].clone();
+            if from.kind == NodeKind::Output
+                || to.kind == NodeKind::Input
+                || to.kind == NodeKind::Bias
+                || self
+                    .connections
+                    .iter()
+                    .any(|c| c.from == from.id && c.to == to.id)
+                || self.reaches(to.id, from.id)
+            {
+                continue;
+            }
+
+            self.connections.push(ConnectionGene {
+                innovation: innovations.connection(from.id, to.id),
+                from: from.id,
+                to: to.id,
+                weight: rand::gen_range(-1.0, 1.0),
+                enabled: true,
+            });
+            return;
+        }
+    }
+
+    /// Splits a random enabled connection in two around a new hidden node. The
+    /// incoming half gets weight 1 and the outgoing half the old weight, so the
+    /// network behaves almost as before.
+    fn add_node(&mut self, innovations: &mut Innovations) {
+        let enabled: Vec<usize> = (0..self.connections.len())
+            .filter(|i| self.connections[*i].enabled)
+            .collect();
+        if enabled.is_empty() {
+            return;
+        }
+        let split = &mut self.connections[enabled[rand::gen_range(0, enabled.len())]];
+        split.enabled = false;
+        let ConnectionGene {
+            innovation,
+            from,
+            to,
+            weight,
+            ..
+        } = split.clone();
+
+        let mut node = innovations.split(innovation);
+        if self.nodes.iter().any(|n| n.id == node) {
+            node = innovations.fresh_node();
+        }
+        self.nodes.push(NodeGene {
+            id: node,
+            kind: NodeKind::Hidden,
+        });
+        self.connections.push(ConnectionGene {
+            innovation: innovations.connection(from, node),
+            from,
+            to: node,
+            weight: 1.0,
+            enabled: true,
+        });
+        self.connections.push(ConnectionGene {
+            innovation: innovations.connection(node, to),
+            from: node,
+            to,
+            weight,
+            enabled: true,
+        });
+    }
+
+    /// NEAT's compatibility distance: excess and disjoint genes, normalised by
+    /// the larger genome, plus the average weight difference of matching genes.
+    pub fn distance(&self, other: &Genome) -> f32 {
+        let ours: HashMap<usize, f32> = self
+            .connections
+            .iter()
+            .map(|c| (c.innovation, c.weight))
+            .collect();
+        let theirs: HashMap<usize, f32> = other
+            .connections
+            .iter()
+            .map(|c| (c.innovation, c.weight))
+            .collect();
+        let our_max = ours.keys().copied().max().unwrap_or(0);
+        let their_max = theirs.keys().copied().max().unwrap_or(0);
+
+        let mut excess = 0;
+        let mut disjoint = 0;
+        let mut weight_difference = 0.0;
+        let mut matching = 0;
+        for (innovation, weight) in ours.iter() {
+            match theirs.get(innovation) {
+                Some(other_weight) => {
+                    matching += 1;
+                    weight_difference += (weight - other_weight).abs();
+                }
+                None if *innovation > their_max => excess += 1,
+                None => disjoint += 1,
+            }
+        }
+        for innovation in theirs.keys().filter(|i| !ours.contains_key(i)) {
+            if *innovation > our_max {
+                excess += 1;
+            } else {
+                disjoint += 1;
+            }
+        }
+
+        let size = ours.len().max(theirs.len()).max(1) as f32;
+        EXCESS_COEFFICIENT * excess as f32 / size
+            + DISJOINT_COEFFICIENT * disjoint as f32 / size
+            + WEIGHT_COEFFICIENT * weight_difference / matching.max(1) as f32
+    }
+}
+
+/// A NEAT species, represented by the genome that founded it.
+#[derive(Debug)]
+struct Species {
+    id: usize,
+    representative: Genome,
+}
+
+/// Groups genomes into species by compatibility distance.
+#[derive(Debug, Default)]
+pub struct Speciation {
+    species: Vec<Species>,
+    next_id: usize,
+}
+
+impl Speciation {
+    /// The first species whose representative is within `threshold` of
+    /// `genome`, or a new species founded by it.
+    pub fn assign(&mut self, genome: &Genome, threshold: f32) -> usize {
+        if let Some(species) = self
+            .species
+            .iter()
+            .find(|s| s.representative.distance(genome) < threshold)
+        {
+            return species.id;
+        }
+
+        self.next_id += 1;
+        self.species.push(Species {
+            id: self.next_id,
+            representative: genome.clone(),
+        });
+        self.next_id
+    }
+
+    /// Forgets species that no longer have members.
+    pub fn prune(&mut self, alive: &HashSet<usize>) {
+        self.species.retain(|s| alive.contains(&s.id));
+    }
+
+    pub fn len(&self) -> usize {
+        self.species.len()
+    }
+}
@@ -0,0 +1,189 @@
+use macroquad::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// A static shape entities can't pass or see through.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub enum Obstacle {
+    Circle {
+        centre: [f32; 2],
+        radius: f32,
+    },
+    Rect {
+        x: f32,
+        y: f32,
+        w: f32,
+        h: f32,
+    },
+    /// A wall following `points`, `thickness` pixels wide.
+    Polyline {
+        points: Vec<[f32; 2]>,
+        thickness: f32,
+    },
+}
+
+impl Obstacle {
+    /// Why the obstacle as written in a config file makes no sense, if it doesn't.
+    pub fn check(&self) -> Result<(), String> {
+        match self {
+            Obstacle::Polyline { points, .. } if points.len() < 2 => Err(format!(
+                "a polyline needs at least 2 points, got {}",
+                points.len()
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// The point on the obstacle's outline nearest to `p`, and whether `p`
+    /// lies inside it. Walls have no inside; their thickness is left to the caller.
+    fn nearest(&self, p: Vec2) -> (Vec2, bool) {
+        match self {
+            Obstacle::Circle { centre, radius } => {
+                let centre = Vec2::from(*centre);
+                let offset = p - centre;
+                let direction = if offset == Vec2::ZERO {
+                    Vec2::X
+                } else {
+                    offset.normalize()
+                };
+                (centre + *radius * direction, offset.length() < *radius)
+            }
+            Obstacle::Rect { x, y, w, h } => {
+                let (min, max) = (Vec2::new(*x, *y), Vec2::new(x + w, y + h));
+                let clamped = p.clamp(min, max);
+                if clamped != p {
+                    return (clamped, false);
+                }
+                // Inside: the nearest point is on the closest edge.
+                let edges = [
+                    (p.x - min.x, Vec2::new(min.x, p.y)),
+                    (max.x - p.x, Vec2::new(max.x, p.y)),
+                    (p.y - min.y, Vec2::new(p.x, min.y)),
+                    (max.y - p.y, Vec2::new(p.x, max.y)),
+                ];
+                let (_, edge) = edges
+                    .into_iter()
+                    .min_by(|a, b| a.0.partial_cmp(&b.0).unwrap())
+                    .unwrap();
+                (edge, true)
+            }
+            Obstacle::Polyline { points, .. } => {
+                let nearest = points
+                    .windows(2)
+                    .map(|s| closest_on_segment(p, s[0].into(), s[1].into()))
+                    .min_by(|a, b| {
+                        p.distance_squared(*a)
+                            .partial_cmp(&p.distance_squared(*b))
+                            .unwrap()
+                    })
+                    .unwrap_or_else(|| points.first().map_or(p, |q| Vec2::from(*q)));
+                (nearest, false)
+            }
+        }
+    }
+
+    /// Half the width of the obstacle's outline.
+    fn half_thickness(&self) -> f32 {
+        match self {
+            Obstacle::Polyline { thickness, .. } => thickness / 2.0,
+            _ => 0.0,
+        }
+    }
+
+    /// The point on the obstacle's surface nearest to `p`.
+    pub fn surface(&self, p: Vec2) -> Vec2 {
+        let (nearest, inside) = self.nearest(p);
+        let away = (p - nearest).normalize_or_zero();
+        nearest + self.half_thickness() * if inside { -away } else { away }
+    }
+
+    /// Where a body of `radius` at `p` ends up once pushed clear of the
+    /// obstacle, or `None` if it isn't touching it.
+    pub fn push_out(&self, p: Vec2, radius: f32) -> Option<Vec2> {
+        let (nearest, inside) = self.nearest(p);
+        let clearance = radius + self.half_thickness();
+        let offset = p - nearest;
+        if inside {
+            Some(nearest - clearance * offset.normalize_or_zero())
+        } else if offset.length_squared() < clearance.powi(2) {
+            let direction = if offset == Vec2::ZERO {
+                Vec2::X
+            } else {
+                offset.normalize()
+            };
+            Some(nearest + clearance * direction)
+        } else {
+            None
+        }
+    }
+
+    /// Whether `p` is inside the obstacle or its wall.
+    pub fn contains(&self, p: Vec2) -> bool {
+        let (nearest, inside) = self.nearest(p);
+        inside || p.distance(nearest) < self.half_thickness()
+    }
+
+    /// Whether the obstacle cuts the line of sight from `a` to `b`.
+    pub fn blocks(&self, a: Vec2, b: Vec2) -> bool {
+        match self {
+            Obstacle::Circle { centre, radius } => {
+                let centre = Vec2::from(*centre);
+                closest_on_segment(centre, a, b).distance_squared(centre) < radius.powi(2)
+            }
+            Obstacle::Rect { x, y, w, h } => {
+                let corners = [
+                    Vec2::new(*x, *y),
+                    Vec2::new(x + w, *y),
+                    Vec2::new(x + w, y + h),
+                    Vec2::new(*x, y + h),
+                ];
+                self.contains(a)
+                    || self.contains(b)
+                    || (0..4).any(|i| segments_cross(a, b, corners[i], corners[(i + 1) % 4]))
+            }
+            Obstacle::Polyline { points, .. } => points
+                .windows(2)
+                .any(|s| segments_cross(a, b, s[0].into(), s[1].into())),
+        }
+    }
+
+    pub fn draw(&self) {
+        let colour = Color::new(0.45, 0.45, 0.5, 1.0);
+        match self {
+            Obstacle::Circle { centre, radius } => {
+                draw_circle(centre[0], centre[1], *radius, colour)
+            }
+            Obstacle::Rect { x, y, w, h } => draw_rectangle(*x, *y, *w, *h, colour),
+            Obstacle::Polyline { points, thickness } => {
+                for s in points.windows(2) {
+                    draw_line(s[0][0], s[0][1], s[1][0], s[1][1], *thickness, colour);
+                }
+                for p in points.iter() {
+                    draw_circle(p[0], p[1], thickness / 2.0, colour);
+                }
+            }
+        }
+    }
+}
+
+/// Whether any of `obstacles` cuts the line of sight from `a` to `b`.
+pub fn blocked(obstacles: &[Obstacle], a: Vec2, b: Vec2) -> bool {
+    obstacles.iter().any(|o| o.blocks(a, b))
+}
+
+fn closest_on_segment(p: Vec2, a: Vec2, b: Vec2) -> Vec2 {
+    let ab = b - a;
+    let length_squared = ab.length_squared();
+    if length_squared == 0.0 {
+        return a;
+    }
+    a + ab * ((p - a).dot(ab) / length_squared).clamp(0.0, 1.0)
+}
+
+/// Whether segments `ab` and `cd` properly cross each other.
+fn segments_cross(a: Vec2, b: Vec2, c: Vec2, d: Vec2) -> bool {
+    let side = |p: Vec2, q: Vec2, r: Vec2| (q - p).perp_dot(r - p);
+    let (d1, d2) = (side(c, d, a), side(c, d, b));
+    let (d3, d4) = (side(a, b, c), side(a, b, d));
+    d1 * d2 < 0.0 && d3 * d4 < 0.0
+}
@@ -0,0 +1,66 @@
+use super::{Config, Entity, Perception, Simulation};
+
+/// Kills made by predators of a species, alone or as a pack.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Hunts {
+    pub solo: u32,
+    pub pack: u32,
+}
+
+impl Entity {
+    /// Whether the entity is social enough to hunt with others of its species.
+    pub(super) fn hunts_in_pack(&self, config: &Config) -> bool {
+        self.genes.coop >= config.pack_coop
+    }
+
+    /// The prey the entity goes after this tick, if it hunts in a pack.
+    ///
+    /// Packs form without a leader being elected: every hunter follows the
+    /// lowest-id packmate within `pack_range` whose quarry it can see too,
+    /// and otherwise keeps its own or picks the nearest prey. Whole packs
+    /// settle on one target within a few ticks.
+    pub(super) fn choose_quarry(&self, perception: &Perception, config: &Config) -> Option<u64> {
+        if !self.hunts_in_pack(config) {
+            return None;
+        }
+        let visible = |id: &u64| perception.prey.iter().any(|p| p.id == *id);
+
+        perception
+            .neighbours
+            .iter()
+            .filter(|n| {
+                n.id < self.id
+                    && n.hunts_in_pack(config)
+                    && self.pos.distance(n.pos) <= config.pack_range
+                    && n.quarry.is_some_and(|q| visible(&q))
+            })
+            .min_by_key(|n| n.id)
+            .and_then(|n| n.quarry)
+            .or(self.quarry.filter(visible))
+            .or(perception.prey.first().map(|p| p.id))
+    }
+}
+
+impl Simulation {
+    /// Ids of `predator`'s pack at the kill of `prey`: every pack hunter of
+    /// its species within `pack_range` that was after the same quarry and
+    /// isn't still handling a kill of its own, the predator included. Empty
+    /// if the predator hunts alone or caught something else than its quarry.
+    pub(super) fn pack(&self, predator: &Entity, prey: u64) -> Vec<u64> {
+        if !predator.hunts_in_pack(&self.config) || predator.quarry != Some(prey) {
+            return vec![];
+        }
+        self.entity_qt
+            .query_around(predator.pos, self.config.pack_range)
+            .into_iter()
+            .filter(|e| {
+                e.species == predator.species
+                    && e.quarry == Some(prey)
+                    && e.hunts_in_pack(&self.config)
+                    && (e.id == predator.id || e.handling == 0)
+                    && predator.pos.distance(e.pos) <= self.config.pack_range
+            })
+            .map(|e| e.id)
+            .collect()
+    }
+}
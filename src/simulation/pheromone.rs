@@ -0,0 +1,154 @@
+use macroquad::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use super::{species::FoodWeb, Entity, Perception};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PheromoneConfig {
+    /// Side of a grid cell in pixels.
+    pub cell_size: f32,
+    /// Fraction of the difference to its neighbours' average a cell evens
+    /// out every tick. Above 1 the field oscillates.
+    pub diffusion: f32,
+    /// Fraction of scent that evaporates every tick.
+    pub evaporation: f32,
+}
+
+impl Default for PheromoneConfig {
+    fn default() -> Self {
+        PheromoneConfig {
+            cell_size: 8.0,
+            diffusion: 0.2,
+            evaporation: 0.01,
+        }
+    }
+}
+
+/// One scent grid per species over the world, each holding what members of
+/// that species left behind as it spreads out and fades.
+#[derive(Debug, Clone)]
+pub struct Pheromones {
+    layers: Vec<Vec<f32>>,
+    columns: usize,
+    rows: usize,
+    cell_size: f32,
+    /// Whether scent spreads across the edges of the world.
+    wraps: bool,
+}
+
+impl Pheromones {
+    pub fn new(config: &PheromoneConfig, species: usize, w: f32, h: f32, wraps: bool) -> Self {
+        let cell_size = config.cell_size.max(1.0);
+        let columns = (w / cell_size).ceil() as usize;
+        let rows = (h / cell_size).ceil() as usize;
+        Pheromones {
+            layers: vec![vec![0.0; columns * rows]; species],
+            columns,
+            rows,
+            cell_size,
+            wraps,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        for layer in self.layers.iter_mut() {
+            layer.fill(0.0);
+        }
+    }
+
+    /// Index of the cell at `column`, `row`, which may be one step off the
+    /// grid: wrapped around in a wrapping world, clamped to the edge otherwise.
+    fn index(&self, column: i64, row: i64) -> usize {
+        let (columns, rows) = (self.columns as i64, self.rows as i64);
+        let (column, row) = if self.wraps {
+            (column.rem_euclid(columns), row.rem_euclid(rows))
+        } else {
+            (column.clamp(0, columns - 1), row.clamp(0, rows - 1))
+        };
+        (row * columns + column) as usize
+    }
+
+    fn cell(&self, pos: Vec2) -> (i64, i64) {
+        (
+            (pos.x / self.cell_size).floor() as i64,
+            (pos.y / self.cell_size).floor() as i64,
+        )
+    }
+
+    /// Leaves `amount` of `species`' scent at `pos`.
+    pub fn deposit(&mut self, species: usize, pos: Vec2, amount: f32) {
+        let (column, row) = self.cell(pos);
+        let i = self.index(column, row);
+        self.layers[species][i] += amount;
+    }
+
+    /// Which way and how steeply `species`' scent rises at `pos`, per pixel.
+    pub fn gradient(&self, species: usize, pos: Vec2) -> Vec2 {
+        let (column, row) = self.cell(pos);
+        let layer = &self.layers[species];
+        let at = |dc: i64, dr: i64| layer[self.index(column + dc, row + dr)];
+        Vec2::new(at(1, 0) - at(-1, 0), at(0, 1) - at(0, -1)) / (2.0 * self.cell_size)
+    }
+
+    /// One tick of spreading to the four neighbouring cells and evaporating.
+    pub fn update(&mut self, config: &PheromoneConfig) {
+        let keep = 1.0 - config.evaporation.clamp(0.0, 1.0);
+        for s in 0..self.layers.len() {
+            let layer = &self.layers[s];
+            let next: Vec<f32> = (0..layer.len())
+                .map(|i| {
+                    let (column, row) = ((i % self.columns) as i64, (i / self.columns) as i64);
+                    let around = [(1, 0), (-1, 0), (0, 1), (0, -1)]
+                        .iter()
+                        .map(|(dc, dr)| layer[self.index(column + dc, row + dr)])
+                        .sum::<f32>()
+                        / 4.0;
+                    keep * (layer[i] + config.diffusion * (around - layer[i]))
+                })
+                .collect();
+            self.layers[s] = next;
+        }
+    }
+
+    /// `species`' scent as a heatmap, brightest where it is strongest.
+    pub fn draw(&self, species: usize, colour: Color) {
+        let layer = &self.layers[species];
+        let strongest = layer.iter().copied().fold(0.0, f32::max);
+        if strongest <= 0.0 {
+            return;
+        }
+        for (i, level) in layer.iter().enumerate() {
+            if *level <= 0.0 {
+                continue;
+            }
+            draw_rectangle(
+                (i % self.columns) as f32 * self.cell_size,
+                (i / self.columns) as f32 * self.cell_size,
+                self.cell_size,
+                self.cell_size,
+                Color {
+                    a: 0.6 * (level / strongest).sqrt(),
+                    ..colour
+                },
+            );
+        }
+    }
+}
+
+impl Entity {
+    /// Fills in the scent gradients `perception` leaves out: that of the
+    /// entity's own species, and the sum over everything it hunts.
+    pub(super) fn smell(
+        &self,
+        pheromones: &Pheromones,
+        web: &FoodWeb,
+        perception: &mut Perception,
+    ) {
+        perception.kin_scent = pheromones.gradient(self.species, self.pos);
+        perception.prey_scent = (0..pheromones.layers.len())
+            .filter(|s| web.eats(self.species, *s))
+            .map(|s| pheromones.gradient(s, self.pos))
+            .fold(Vec2::ZERO, |a, b| a + b);
+    }
+}
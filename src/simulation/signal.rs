@@ -0,0 +1,101 @@
+use macroquad::prelude::*;
+
+use super::{Entity, Perception, QuadTree, QuadTreeItem, Simulation};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SignalKind {
+    /// Warns of a threat.
+    Alarm,
+    /// Calls others over to food.
+    Food,
+}
+
+/// A call made by `sender`, heard by its species within `radius` during the
+/// next tick.
+#[derive(Debug, Clone)]
+pub struct Signal {
+    pub kind: SignalKind,
+    pub pos: Vec2,
+    pub sender: u64,
+    pub species: usize,
+    pub radius: f32,
+}
+
+impl QuadTreeItem for Signal {
+    fn pos(&self) -> Vec2 {
+        self.pos
+    }
+    fn shift(&mut self, offset: Vec2) {
+        self.pos += offset;
+    }
+    fn draw(&self) {
+        let colour = match self.kind {
+            SignalKind::Alarm => Color::new(1.0, 0.3, 0.3, 0.25),
+            SignalKind::Food => Color::new(0.3, 1.0, 0.3, 0.25),
+        };
+        draw_circle_lines(self.pos.x, self.pos.y, self.radius, 1.0, colour);
+    }
+    fn debug(&self) {}
+}
+
+impl Entity {
+    /// What the entity calls out this tick, if anything. With a threat in
+    /// sight its `alarm` gene is the chance it raises the alarm, and with food
+    /// in sight `food_call` the chance it calls others over. Otherwise its
+    /// `false_call` gene is the chance it makes a call it has no cue for, so
+    /// honesty is something selection can act on rather than a given.
+    pub(super) fn signal(&self, perception: &Perception) -> Option<SignalKind> {
+        let chance = |p: f32| rand::gen_range(0.0, 1.0) < p;
+        let threat = !perception.threats.is_empty();
+        let food = !perception.targets.is_empty();
+        if threat && chance(self.genes.alarm) {
+            Some(SignalKind::Alarm)
+        } else if food && chance(self.genes.food_call) {
+            Some(SignalKind::Food)
+        } else if chance(self.genes.false_call) {
+            match (threat, food) {
+                (false, false) if chance(0.5) => Some(SignalKind::Alarm),
+                (false, false) => Some(SignalKind::Food),
+                (false, true) => Some(SignalKind::Alarm),
+                (true, false) => Some(SignalKind::Food),
+                (true, true) => None,
+            }
+        } else {
+            None
+        }
+    }
+
+    /// Adds the calls of its own species that reach the entity to `perception`.
+    /// `radius` is the configured `signal_radius`, which every call shares.
+    pub(super) fn hear(
+        &self,
+        signals: &QuadTree<Signal>,
+        radius: f32,
+        perception: &mut Perception,
+    ) {
+        for signal in signals.query_around(self.pos, radius) {
+            if signal.sender == self.id
+                || signal.species != self.species
+                || self.pos.distance(signal.pos) > signal.radius
+            {
+                continue;
+            }
+            match signal.kind {
+                SignalKind::Alarm => perception.alarms.push(signal.pos),
+                SignalKind::Food => perception.calls.push(signal.pos),
+            }
+        }
+    }
+}
+
+impl Simulation {
+    /// Makes the calls of the last tick audible and starts collecting this
+    /// tick's.
+    pub(super) fn broadcast(&mut self) {
+        self.signal_qt =
+            QuadTree::new(self.boundary, self.capacity).wrapping(self.config.edges.wraps());
+        for signal in self.signals.drain(..) {
+            self.signal_qt.insert(signal);
+        }
+    }
+}
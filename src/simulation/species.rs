@@ -0,0 +1,361 @@
+use macroquad::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    behaviour::{
+        Align, AvoidObstacles, Cohere, Encircle, Flee, Flock, FollowTrail, HeedAlarm, HeedCall,
+        Recall, Seek, SeekMate, Separate, ShareFood, Track, Weighted,
+    },
+    genes::Genes,
+    mating::Reproduction,
+    terrain::{PerCell, TerrainEffect},
+    wander::Search,
+    FoodKind,
+};
+
+/// Diet entry standing for growing food items rather than another species.
+pub const PLANTS: &str = "plants";
+/// Diet entry standing for the carcasses dead entities leave behind.
+pub const CARRION: &str = "carrion";
+
+/// A kind of entity, as described in the scenario file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Species {
+    pub name: String,
+    /// RGB, each channel in `[0, 1]`.
+    pub colour: [f32; 3],
+    /// Body radius in pixels.
+    pub size: f32,
+    /// Names of the species this one eats, `"plants"` for food items and
+    /// `"carrion"` for carcasses.
+    pub diet: Vec<String>,
+    pub behaviours: Vec<BehaviourSpec>,
+    /// Genes the species' founders are spawned with.
+    pub genes: Genes,
+    /// Whether founders get random `coop` and `share` genes instead of those
+    /// in `genes`, so selection has variation to work on from the start.
+    pub random_social: bool,
+    /// Nutrition an entity of this species gives whoever eats it.
+    pub nutrition: f32,
+    /// How many are spawned when the world is (re)populated.
+    pub initial_count: usize,
+    /// Whether offspring come from one parent or two.
+    pub reproduction: Reproduction,
+    /// Distance within which two ready entities of a sexual species pair up.
+    pub mating_range: f32,
+    /// Ticks a pair stands together before their child is born.
+    pub courtship_time: u32,
+    /// Ticks after having a child before an entity may court again.
+    pub mating_cooldown: u32,
+    /// Ticks an entity can live at most; 0 means it never dies of old age.
+    pub lifespan: u32,
+    /// Chance per tick of dying of old age right before the end of the
+    /// lifespan. Younger entities run a smaller risk, rising with the cube of age.
+    pub senescence: f32,
+    /// Age at which juveniles grow up and may reproduce.
+    pub maturity_age: u32,
+    /// Fraction of their top speed juveniles can reach.
+    pub juvenile_speed: f32,
+    /// Fraction of an overlap with another entity resolved each tick: 0 lets
+    /// bodies pass through each other, 1 makes them solid. A pair uses the
+    /// lower of their two values.
+    pub stiffness: f32,
+    /// How each kind of terrain slows, tires and hides things from this species.
+    pub terrain: PerCell<TerrainEffect>,
+    /// Scent each member leaves where it stands every tick.
+    pub scent: f32,
+    /// How members search when they see nothing to eat or flee from.
+    pub search: Search,
+}
+
+impl Default for Species {
+    fn default() -> Self {
+        Species {
+            name: "unnamed".to_owned(),
+            colour: [1.0, 1.0, 1.0],
+            size: 5.0,
+            diet: vec![],
+            behaviours: vec![BehaviourSpec {
+                weight: 1.0,
+                behaviour: BehaviourKind::Seek,
+            }],
+            genes: Genes {
+                coop: 0.5,
+                share: 0.5,
+                max_speed: 1.2,
+                max_turn_rate: 0.1,
+                max_acceleration: 0.05,
+                vision_range: 100.0,
+                vision_angle: 180f32.to_radians(),
+                hearing_range: 25.0,
+                alarm: 0.0,
+                food_call: 0.0,
+                false_call: 0.0,
+                kin: Default::default(),
+            },
+            nutrition: 4.0,
+            random_social: false,
+            initial_count: 0,
+            reproduction: Reproduction::Asexual,
+            mating_range: 15.0,
+            courtship_time: 30,
+            mating_cooldown: 300,
+            lifespan: 10000,
+            senescence: 0.002,
+            maturity_age: 300,
+            juvenile_speed: 0.6,
+            stiffness: 0.5,
+            terrain: PerCell::default(),
+            scent: 1.0,
+            search: Search::default(),
+        }
+    }
+}
+
+impl Species {
+    pub fn colour(&self) -> Color {
+        let [r, g, b] = self.colour;
+        Color::new(r, g, b, 1.0)
+    }
+
+    /// Prey eating plants and fleeing predators, as the world started out.
+    pub fn default_prey() -> Self {
+        Species {
+            name: "prey".to_owned(),
+            colour: [0.0, 0.47, 0.95],
+            diet: vec![PLANTS.to_owned()],
+            behaviours: vec![
+                BehaviourSpec {
+                    weight: 1.0,
+                    behaviour: BehaviourKind::Seek,
+                },
+                BehaviourSpec {
+                    weight: 2.0,
+                    behaviour: BehaviourKind::Flee,
+                },
+                BehaviourSpec {
+                    weight: 2.0,
+                    behaviour: BehaviourKind::AvoidObstacles { range: 25.0 },
+                },
+                BehaviourSpec {
+                    weight: 0.2,
+                    behaviour: BehaviourKind::Recall { home: 0.0 },
+                },
+                BehaviourSpec {
+                    weight: 0.05,
+                    behaviour: BehaviourKind::Flock {
+                        separation: 8.0,
+                        alignment: 1.0,
+                        cohesion: 1.0,
+                    },
+                },
+            ],
+            genes: Genes {
+                coop: 0.5,
+                share: 0.5,
+                max_speed: 1.2,
+                max_turn_rate: 0.15,
+                max_acceleration: 0.06,
+                vision_range: 80.0,
+                vision_angle: 300f32.to_radians(),
+                hearing_range: 30.0,
+                alarm: 0.0,
+                food_call: 0.0,
+                false_call: 0.0,
+                kin: Default::default(),
+            },
+            random_social: true,
+            initial_count: 500,
+            ..Default::default()
+        }
+    }
+
+    /// Predators hunting the default prey.
+    pub fn default_predator() -> Self {
+        Species {
+            name: "predator".to_owned(),
+            colour: [0.9, 0.16, 0.22],
+            diet: vec!["prey".to_owned()],
+            behaviours: vec![
+                BehaviourSpec {
+                    weight: 1.0,
+                    behaviour: BehaviourKind::Seek,
+                },
+                BehaviourSpec {
+                    weight: 2.0,
+                    behaviour: BehaviourKind::AvoidObstacles { range: 25.0 },
+                },
+                BehaviourSpec {
+                    weight: 0.2,
+                    behaviour: BehaviourKind::Recall { home: 0.0 },
+                },
+            ],
+            genes: Genes {
+                coop: 0.5,
+                share: 0.5,
+                max_speed: 1.4,
+                max_turn_rate: 0.08,
+                max_acceleration: 0.04,
+                vision_range: 120.0,
+                vision_angle: 100f32.to_radians(),
+                hearing_range: 20.0,
+                alarm: 0.0,
+                food_call: 0.0,
+                false_call: 0.0,
+                kin: Default::default(),
+            },
+            random_social: true,
+            initial_count: 500,
+            ..Default::default()
+        }
+    }
+
+    /// The steering composition entities of this species follow.
+    pub fn build_behaviours(&self) -> Vec<Weighted> {
+        self.behaviours.iter().map(|spec| spec.build()).collect()
+    }
+}
+
+/// A behaviour and its weight, as written in the scenario file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BehaviourSpec {
+    pub weight: f32,
+    pub behaviour: BehaviourKind,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub enum BehaviourKind {
+    Seek,
+    Flee,
+    Align,
+    Cohere,
+    Separate {
+        radius: f32,
+    },
+    AvoidObstacles {
+        range: f32,
+    },
+    SeekMate,
+    ShareFood,
+    Encircle {
+        radius: f32,
+    },
+    Flock {
+        separation: f32,
+        alignment: f32,
+        cohesion: f32,
+    },
+    Track,
+    FollowTrail,
+    HeedAlarm,
+    HeedCall,
+    Recall {
+        home: f32,
+    },
+}
+
+impl BehaviourSpec {
+    fn build(&self) -> Weighted {
+        match self.behaviour {
+            BehaviourKind::Seek => Weighted::new(self.weight, Seek),
+            BehaviourKind::Flee => Weighted::new(self.weight, Flee),
+            BehaviourKind::Align => Weighted::new(self.weight, Align),
+            BehaviourKind::Cohere => Weighted::new(self.weight, Cohere),
+            BehaviourKind::Separate { radius } => Weighted::new(self.weight, Separate { radius }),
+            BehaviourKind::AvoidObstacles { range } => {
+                Weighted::new(self.weight, AvoidObstacles { range })
+            }
+            BehaviourKind::SeekMate => Weighted::new(self.weight, SeekMate),
+            BehaviourKind::ShareFood => Weighted::new(self.weight, ShareFood),
+            BehaviourKind::Track => Weighted::new(self.weight, Track),
+            BehaviourKind::HeedAlarm => Weighted::new(self.weight, HeedAlarm),
+            BehaviourKind::HeedCall => Weighted::new(self.weight, HeedCall),
+            BehaviourKind::Recall { home } => Weighted::new(self.weight, Recall { home }),
+            BehaviourKind::FollowTrail => Weighted::new(self.weight, FollowTrail),
+            BehaviourKind::Encircle { radius } => Weighted::new(self.weight, Encircle { radius }),
+            BehaviourKind::Flock {
+                separation,
+                alignment,
+                cohesion,
+            } => Weighted::new(
+                self.weight,
+                Flock {
+                    separation,
+                    alignment,
+                    cohesion,
+                },
+            ),
+        }
+    }
+}
+
+/// Who eats whom, resolved from the species' diets.
+#[derive(Debug, Clone)]
+pub struct FoodWeb {
+    /// `eats[a][b]`: species `a` eats species `b`.
+    eats: Vec<Vec<bool>>,
+    /// `plants[a]`: species `a` eats growing food items.
+    plants: Vec<bool>,
+    /// `carrion[a]`: species `a` scavenges carcasses.
+    carrion: Vec<bool>,
+}
+
+impl FoodWeb {
+    pub fn new(species: &[Species]) -> Result<Self, String> {
+        if species.is_empty() {
+            return Err("the scenario has no species".to_owned());
+        }
+        let mut eats = vec![vec![false; species.len()]; species.len()];
+        let mut plants = vec![false; species.len()];
+        let mut carrion = vec![false; species.len()];
+
+        for (i, s) in species.iter().enumerate() {
+            if species[..i].iter().any(|other| other.name == s.name) {
+                return Err(format!("species {} is defined twice", s.name));
+            }
+        }
+
+        for (eater, s) in species.iter().enumerate() {
+            for item in s.diet.iter() {
+                if item == PLANTS {
+                    plants[eater] = true;
+                } else if item == CARRION {
+                    carrion[eater] = true;
+                } else {
+                    let prey = species
+                        .iter()
+                        .position(|p| &p.name == item)
+                        .ok_or_else(|| format!("{} eats unknown species {}", s.name, item))?;
+                    eats[eater][prey] = true;
+                }
+            }
+        }
+
+        Ok(FoodWeb {
+            eats,
+            plants,
+            carrion,
+        })
+    }
+
+    /// Whether species `eater` eats species `prey`.
+    pub fn eats(&self, eater: usize, prey: usize) -> bool {
+        self.eats[eater][prey]
+    }
+
+    /// Whether species `eater` eats food items of `kind`.
+    pub fn eats_food(&self, eater: usize, kind: FoodKind) -> bool {
+        match kind {
+            FoodKind::Plant => self.plants[eater],
+            FoodKind::Carcass => self.carrion[eater],
+        }
+    }
+
+    /// Whether species `eater` eats any kind of food item.
+    pub fn forages(&self, eater: usize) -> bool {
+        self.plants[eater] || self.carrion[eater]
+    }
+}
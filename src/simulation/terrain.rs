@@ -0,0 +1,260 @@
+use macroquad::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// What the ground is like in one cell of the terrain grid.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Cell {
+    Grass,
+    Water,
+    Forest,
+    Rock,
+}
+
+impl Cell {
+    /// Colour the cell is drawn with, and matched against in terrain images.
+    fn colour(&self) -> Color {
+        match self {
+            Cell::Grass => Color::from_rgba(70, 140, 60, 255),
+            Cell::Water => Color::from_rgba(40, 90, 200, 255),
+            Cell::Forest => Color::from_rgba(20, 80, 30, 255),
+            Cell::Rock => Color::from_rgba(130, 130, 130, 255),
+        }
+    }
+
+    /// The cell whose colour is closest to `colour`.
+    fn nearest(colour: Color) -> Cell {
+        let distance = |c: Color| {
+            (c.r - colour.r).powi(2) + (c.g - colour.g).powi(2) + (c.b - colour.b).powi(2)
+        };
+        [Cell::Grass, Cell::Water, Cell::Forest, Cell::Rock]
+            .into_iter()
+            .min_by(|a, b| {
+                distance(a.colour())
+                    .partial_cmp(&distance(b.colour()))
+                    .unwrap()
+            })
+            .unwrap()
+    }
+}
+
+/// One value for each kind of cell.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PerCell<T> {
+    pub grass: T,
+    pub water: T,
+    pub forest: T,
+    pub rock: T,
+}
+
+impl<T> PerCell<T> {
+    pub fn get(&self, cell: Cell) -> &T {
+        match cell {
+            Cell::Grass => &self.grass,
+            Cell::Water => &self.water,
+            Cell::Forest => &self.forest,
+            Cell::Rock => &self.rock,
+        }
+    }
+}
+
+/// How a kind of cell affects an entity of some species standing in it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TerrainEffect {
+    /// Multiplier on the distance covered per tick.
+    pub speed: f32,
+    /// Multiplier on the energy spent moving.
+    pub energy: f32,
+    /// How far into this terrain the species can see, as a fraction of its
+    /// vision range.
+    pub visibility: f32,
+}
+
+impl Default for TerrainEffect {
+    fn default() -> Self {
+        TerrainEffect {
+            speed: 1.0,
+            energy: 1.0,
+            visibility: 1.0,
+        }
+    }
+}
+
+impl Default for PerCell<TerrainEffect> {
+    fn default() -> Self {
+        PerCell {
+            grass: TerrainEffect::default(),
+            water: TerrainEffect {
+                speed: 0.3,
+                energy: 2.0,
+                visibility: 1.0,
+            },
+            forest: TerrainEffect {
+                speed: 0.7,
+                energy: 1.2,
+                visibility: 0.4,
+            },
+            rock: TerrainEffect {
+                speed: 0.5,
+                energy: 1.5,
+                visibility: 1.0,
+            },
+        }
+    }
+}
+
+/// Where the terrain grid comes from.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub enum TerrainSource {
+    /// Grass everywhere.
+    Flat,
+    /// Value noise; `scale` is roughly the size of a patch in pixels.
+    Noise { seed: u32, scale: f32 },
+    /// A PNG stretched over the world, each pixel read as the cell whose
+    /// colour is closest.
+    Image { path: String },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TerrainConfig {
+    pub source: TerrainSource,
+    /// Side of a grid cell in pixels.
+    pub cell_size: f32,
+    /// Chance, relative to grass, that food sprouting on a cell takes root.
+    pub fertility: PerCell<f32>,
+}
+
+impl Default for TerrainConfig {
+    fn default() -> Self {
+        TerrainConfig {
+            source: TerrainSource::Flat,
+            cell_size: 10.0,
+            fertility: PerCell {
+                grass: 1.0,
+                water: 0.0,
+                forest: 0.5,
+                rock: 0.0,
+            },
+        }
+    }
+}
+
+/// A grid of cells covering the world.
+#[derive(Debug, Clone)]
+pub struct Terrain {
+    cells: Vec<Cell>,
+    columns: usize,
+    rows: usize,
+    cell_size: f32,
+    /// Whether every cell is grass, in which case nothing is drawn.
+    uniform: bool,
+}
+
+impl Terrain {
+    pub fn new(config: &TerrainConfig, w: f32, h: f32) -> Result<Self, String> {
+        let cell_size = config.cell_size.max(1.0);
+        let columns = (w / cell_size).ceil() as usize;
+        let rows = (h / cell_size).ceil() as usize;
+        let centre = |i: usize| {
+            Vec2::new((i % columns) as f32 + 0.5, (i / columns) as f32 + 0.5) * cell_size
+        };
+
+        let cells: Vec<Cell> = match &config.source {
+            TerrainSource::Flat => vec![Cell::Grass; columns * rows],
+            TerrainSource::Noise { seed, scale } => (0..columns * rows)
+                .map(|i| match fractal_noise(centre(i) / scale.max(1.0), *seed) {
+                    n if n < 0.38 => Cell::Water,
+                    n if n < 0.6 => Cell::Grass,
+                    n if n < 0.72 => Cell::Forest,
+                    _ => Cell::Rock,
+                })
+                .collect(),
+            TerrainSource::Image { path } => {
+                let bytes = std::fs::read(path).map_err(|e| format!("{}: {}", path, e))?;
+                let image = Image::from_file_with_format(&bytes, Some(ImageFormat::Png))
+                    .map_err(|e| format!("{}: {}", path, e))?;
+                (0..columns * rows)
+                    .map(|i| {
+                        let p = centre(i);
+                        let x = (p.x / w * image.width as f32) as u32;
+                        let y = (p.y / h * image.height as f32) as u32;
+                        Cell::nearest(image.get_pixel(
+                            x.min(image.width as u32 - 1),
+                            y.min(image.height as u32 - 1),
+                        ))
+                    })
+                    .collect()
+            }
+        };
+
+        Ok(Terrain {
+            uniform: cells.iter().all(|c| *c == Cell::Grass),
+            cells,
+            columns,
+            rows,
+            cell_size,
+        })
+    }
+
+    /// The cell under `pos`. Positions off the grid wrap around, to match
+    /// copies of entities seen across the edges of a wrapping world.
+    pub fn at(&self, pos: Vec2) -> Cell {
+        let column = ((pos.x / self.cell_size).floor() as i64).rem_euclid(self.columns as i64);
+        let row = ((pos.y / self.cell_size).floor() as i64).rem_euclid(self.rows as i64);
+        self.cells[row as usize * self.columns + column as usize]
+    }
+
+    pub fn draw(&self) {
+        if self.uniform {
+            return;
+        }
+        for (i, cell) in self.cells.iter().enumerate() {
+            let mut colour = cell.colour();
+            colour.a = 0.35;
+            draw_rectangle(
+                (i % self.columns) as f32 * self.cell_size,
+                (i / self.columns) as f32 * self.cell_size,
+                self.cell_size,
+                self.cell_size,
+                colour,
+            );
+        }
+    }
+}
+
+/// A pseudo-random value in `[0, 1]` for a lattice point.
+fn lattice(x: i32, y: i32, seed: u32) -> f32 {
+    let mut h = (x as u32).wrapping_mul(374_761_393)
+        ^ (y as u32).wrapping_mul(668_265_263)
+        ^ seed.wrapping_mul(2_246_822_519);
+    h = (h ^ (h >> 13)).wrapping_mul(1_274_126_177);
+    h ^= h >> 16;
+    h as f32 / u32::MAX as f32
+}
+
+/// Smoothly interpolated lattice values.
+fn value_noise(p: Vec2, seed: u32) -> f32 {
+    let (x, y) = (p.x.floor() as i32, p.y.floor() as i32);
+    let f = p - p.floor();
+    let s = f * f * (3.0 - 2.0 * f);
+    let top = lattice(x, y, seed) + s.x * (lattice(x + 1, y, seed) - lattice(x, y, seed));
+    let bottom =
+        lattice(x, y + 1, seed) + s.x * (lattice(x + 1, y + 1, seed) - lattice(x, y + 1, seed));
+    top + s.y * (bottom - top)
+}
+
+/// Three octaves of value noise, in `[0, 1]`.
+fn fractal_noise(p: Vec2, seed: u32) -> f32 {
+    let mut total = 0.0;
+    let mut amplitude = 1.0;
+    let mut weight = 0.0;
+    for octave in 0..3 {
+        total += amplitude * value_noise(p * 2f32.powi(octave), seed.wrapping_add(octave as u32));
+        weight += amplitude;
+        amplitude /= 2.0;
+    }
+    total / weight
+}
@@ -0,0 +1,139 @@
+use std::f32::consts::PI;
+
+use macroquad::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// How an entity searches when it sees nothing to eat or flee from.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub enum Search {
+    /// Keeps its heading forever.
+    Straight,
+    /// Correlated random walk: the heading drifts by up to `jitter` radians
+    /// every tick.
+    Correlated { jitter: f32 },
+    /// Reynolds' wander: heads for a target on a circle of `radius` held
+    /// `distance` ahead, the target sliding around the circle by up to
+    /// `jitter` radians every tick.
+    Circle {
+        distance: f32,
+        radius: f32,
+        jitter: f32,
+    },
+    /// Lévy flight: straight flights in random directions whose lengths in
+    /// ticks follow a power law with exponent `mu`, between 1 and 3, and are
+    /// never shorter than `min_flight`. Lower `mu` means more long flights.
+    Levy { mu: f32, min_flight: f32 },
+}
+
+impl Default for Search {
+    fn default() -> Self {
+        Search::Circle {
+            distance: 30.0,
+            radius: 15.0,
+            jitter: 0.5,
+        }
+    }
+}
+
+/// Where an entity is in its search, carried between ticks.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Wandering {
+    /// Position of the wander circle's target, relative to the heading.
+    angle: f32,
+    /// Ticks left in the current Lévy flight.
+    flight: u32,
+    /// Heading of the current Lévy flight.
+    heading: f32,
+}
+
+/// Longest Lévy flight, so a heavy tail can't send an entity off for good.
+const MAX_FLIGHT: f32 = 5000.0;
+
+impl Wandering {
+    /// The heading to take this tick when travelling along `direction`, and
+    /// the search state for the next one.
+    pub fn next(&self, search: &Search, direction: f32) -> (f32, Wandering) {
+        match *search {
+            Search::Straight => (direction, *self),
+            Search::Correlated { jitter } => (direction + rand::gen_range(-jitter, jitter), *self),
+            Search::Circle {
+                distance,
+                radius,
+                jitter,
+            } => {
+                let angle = self.angle + rand::gen_range(-jitter, jitter);
+                let target = Vec2::from_angle(direction) * distance
+                    + Vec2::from_angle(direction + angle) * radius;
+                (target.to_angle(), Wandering { angle, ..*self })
+            }
+            Search::Levy { mu, min_flight } => {
+                if self.flight > 0 {
+                    return (
+                        self.heading,
+                        Wandering {
+                            flight: self.flight - 1,
+                            ..*self
+                        },
+                    );
+                }
+                // Inverse transform sampling of a Pareto distribution.
+                let u: f32 = rand::gen_range(f32::EPSILON, 1.0);
+                let length = min_flight.max(1.0) * u.powf(-1.0 / (mu - 1.0).max(0.01));
+                let heading = rand::gen_range(-PI, PI);
+                (
+                    heading,
+                    Wandering {
+                        flight: length.min(MAX_FLIGHT) as u32,
+                        heading,
+                        ..*self
+                    },
+                )
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn levy(mu: f32, min_flight: f32) -> Search {
+        Search::Levy { mu, min_flight }
+    }
+
+    #[test]
+    fn levy_flights_are_between_min_flight_and_max_flight() {
+        for _ in 0..10_000 {
+            let (_, wandering) = Wandering::default().next(&levy(2.0, 10.0), 0.0);
+            assert!(wandering.flight >= 10);
+            assert!(wandering.flight as f32 <= MAX_FLIGHT);
+        }
+    }
+
+    #[test]
+    fn levy_flights_with_mu_at_most_one_stay_capped() {
+        for mu in [1.0, 0.5, 0.0, -1.0] {
+            for _ in 0..1000 {
+                let (heading, wandering) = Wandering::default().next(&levy(mu, 1.0), 0.0);
+                assert!(heading.is_finite());
+                assert!(wandering.flight >= 1);
+                assert!(wandering.flight as f32 <= MAX_FLIGHT);
+            }
+        }
+    }
+
+    #[test]
+    fn levy_flight_counts_down_on_its_heading() {
+        let search = levy(2.0, 5.0);
+        let (heading, mut wandering) = Wandering::default().next(&search, 0.0);
+        for left in (0..wandering.flight).rev() {
+            let (next, after) = wandering.next(&search, 1.0);
+            assert_eq!(next, heading);
+            assert_eq!(after.flight, left);
+            wandering = after;
+        }
+        let (_, after) = wandering.next(&search, 1.0);
+        assert!(after.flight >= 5);
+    }
+}